@@ -0,0 +1,245 @@
+#[cfg(target_os = "linux")]
+use socketcan::CanFilter;
+
+const STANDARD_ID_MAX: u32 = 0x7FF;
+const EXTENDED_ID_MAX: u32 = 0x1FFFFFFF;
+#[cfg(any(test, target_os = "linux"))]
+const CAN_EFF_FLAG: u32 = 0x80000000;
+
+/// ID/mask filter with the semantics of `CAN_RAW_FILTER`: a frame of the same format matches
+/// if `frame_id & mask == id & mask`, an inverted filter matches all other frames including the
+/// frames of the other format.
+#[derive(Clone, Copy, PartialEq)]
+pub struct AcceptanceFilter {
+    pub id: u32,
+    pub mask: u32,
+    pub is_extended: bool,
+    pub inverted: bool,
+}
+
+impl AcceptanceFilter {
+    pub fn parse(content: &str) -> Result<Vec<AcceptanceFilter>, String> {
+        let mut filters = Vec::new();
+        for filter in content.split([',', '\n']).map(str::trim) {
+            if filter.is_empty() {
+                continue;
+            }
+            let err = |reason: &str| format!("Invalid filter {} ({})", filter, reason);
+            let mut fields = filter.split_whitespace();
+            let id_mask = fields.next().unwrap_or_default();
+            let mut is_extended = false;
+            let mut inverted = false;
+            for option in fields {
+                match option {
+                    "ext" => is_extended = true,
+                    "inv" => inverted = true,
+                    _ => return Err(err(option)),
+                }
+            }
+            let id_max = if is_extended {
+                EXTENDED_ID_MAX
+            } else {
+                STANDARD_ID_MAX
+            };
+            let (id, mask) = match id_mask.split_once('/') {
+                Some((id, mask)) => (id, Some(mask)),
+                None => (id_mask, None),
+            };
+            let id = Self::parse_hex(id).ok_or_else(|| err("ID"))?;
+            let mask = match mask {
+                Some(mask) => Self::parse_hex(mask).ok_or_else(|| err("mask"))?,
+                None => id_max,
+            };
+            if id > id_max || mask > id_max {
+                return Err(err("out of range"));
+            }
+            filters.push(AcceptanceFilter {
+                id,
+                mask,
+                is_extended,
+                inverted,
+            });
+        }
+        Ok(filters)
+    }
+
+    fn parse_hex(value: &str) -> Option<u32> {
+        let value = value.trim_start_matches("0x").trim_start_matches("0X");
+        u32::from_str_radix(value, 16).ok()
+    }
+
+    pub fn matches(&self, id: u32, is_extended: bool) -> bool {
+        let matches = is_extended == self.is_extended && id & self.mask == self.id & self.mask;
+        matches != self.inverted
+    }
+
+    pub fn accepts(filters: &[AcceptanceFilter], id: u32, is_extended: bool) -> bool {
+        filters.is_empty() || filters.iter().any(|filter| filter.matches(id, is_extended))
+    }
+
+    /// Kernel filter, the frame format flag is part of the mask so standard filters don't
+    /// match extended frames and vice versa. `CAN_INV_FILTER` inverts the whole comparison, so
+    /// inverted filters pass the frames of the other format, as in `matches`.
+    #[cfg(target_os = "linux")]
+    pub fn to_can_filter(self) -> CanFilter {
+        let (id, mask) = self.kernel_id_mask();
+        if self.inverted {
+            CanFilter::new_inverted(id, mask)
+        } else {
+            CanFilter::new(id, mask)
+        }
+    }
+
+    #[cfg(any(test, target_os = "linux"))]
+    fn kernel_id_mask(&self) -> (u32, u32) {
+        let flag = if self.is_extended { CAN_EFF_FLAG } else { 0 };
+        (self.id | flag, self.mask | CAN_EFF_FLAG)
+    }
+
+    /// Code/mask of the PCAN acceptance registers for standard and extended frames. A register
+    /// holds one filter of its format, PCAN mask bits set to 1 are "don't care". Registers which
+    /// can't represent the list are left open, the frames are then filtered in software.
+    #[cfg(any(test, target_os = "windows"))]
+    pub fn pcan_code_masks(filters: &[AcceptanceFilter]) -> ((u32, u32), (u32, u32)) {
+        let inverted = filters.iter().any(|filter| filter.inverted);
+        let register = |is_extended: bool, id_max: u32| {
+            let mut format_filters = filters.iter().filter(|f| f.is_extended == is_extended);
+            match (format_filters.next(), format_filters.next()) {
+                (Some(filter), None) if !inverted => {
+                    (filter.id & filter.mask, !filter.mask & id_max)
+                }
+                _ => (0, id_max),
+            }
+        };
+        (
+            register(false, STANDARD_ID_MAX),
+            register(true, EXTENDED_ID_MAX),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(content: &str) -> AcceptanceFilter {
+        let filters = AcceptanceFilter::parse(content).unwrap();
+        assert_eq!(filters.len(), 1);
+        filters[0]
+    }
+
+    /// `can_rcv_filter` of the kernel for a frame ID with the format flag
+    fn kernel_matches(filter: &AcceptanceFilter, id: u32, is_extended: bool) -> bool {
+        let (filter_id, mask) = filter.kernel_id_mask();
+        let can_id = if is_extended { id | CAN_EFF_FLAG } else { id };
+        (can_id & mask == filter_id & mask) != filter.inverted
+    }
+
+    #[test]
+    fn parses_filters() {
+        let filters =
+            AcceptanceFilter::parse("123/7FF, 18FF0000/1FFF0000 ext\n0x100/700 inv ext").unwrap();
+        assert_eq!(filters.len(), 3);
+        assert!(filters[0].id == 0x123 && filters[0].mask == 0x7FF);
+        assert!(!filters[0].is_extended && !filters[0].inverted);
+        assert!(filters[1].id == 0x18FF0000 && filters[1].mask == 0x1FFF0000);
+        assert!(filters[1].is_extended && !filters[1].inverted);
+        assert!(filters[2].id == 0x100 && filters[2].is_extended && filters[2].inverted);
+        assert_eq!(filter("7E8").mask, 0x7FF);
+        assert_eq!(filter("7E8 ext").mask, 0x1FFFFFFF);
+        assert!(AcceptanceFilter::parse(" , ").unwrap().is_empty());
+        assert!(AcceptanceFilter::parse("800").is_err());
+        assert!(AcceptanceFilter::parse("123/800").is_err());
+        assert!(AcceptanceFilter::parse("12/zz").is_err());
+        assert!(AcceptanceFilter::parse("12 foo").is_err());
+    }
+
+    #[test]
+    fn matches_ids_and_format() {
+        let standard = filter("120/7F0");
+        assert!(standard.matches(0x12F, false));
+        assert!(!standard.matches(0x130, false));
+        assert!(!standard.matches(0x123, true));
+        let inverted = filter("100/700 inv");
+        assert!(!inverted.matches(0x1FF, false));
+        assert!(inverted.matches(0x200, false));
+        assert!(inverted.matches(0x100, true));
+        let extended = filter("18FF0000/1FFF0000 ext inv");
+        assert!(!extended.matches(0x18FF1234, true));
+        assert!(extended.matches(0x18FE1234, true));
+        assert!(extended.matches(0x123, false));
+    }
+
+    #[test]
+    fn agrees_with_kernel_filter() {
+        let filters =
+            AcceptanceFilter::parse("120/7F0, 100/700 inv, 18FF0000/1FFF0000 ext, 7FF ext inv")
+                .unwrap();
+        for filter in &filters {
+            for id in [0x0, 0x100, 0x123, 0x7FF, 0x18FF0000, 0x18FE0100, 0x1FFFFFFF] {
+                for is_extended in [false, true] {
+                    if !is_extended && id > STANDARD_ID_MAX {
+                        continue;
+                    }
+                    assert_eq!(
+                        filter.matches(id, is_extended),
+                        kernel_matches(filter, id, is_extended),
+                        "{:X} ext {}",
+                        id,
+                        is_extended
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn maps_pcan_registers() {
+        let open = ((0, STANDARD_ID_MAX), (0, EXTENDED_ID_MAX));
+        assert_eq!(AcceptanceFilter::pcan_code_masks(&[]), open);
+        let registers = |content: &str| {
+            AcceptanceFilter::pcan_code_masks(&AcceptanceFilter::parse(content).unwrap())
+        };
+        assert_eq!(registers("123"), ((0x123, 0), open.1));
+        assert_eq!(
+            registers("12F/7F0, 18FF0000/1FFF0000 ext"),
+            ((0x120, 0x00F), (0x18FF0000, 0x0000FFFF))
+        );
+        // Two filters of a format or an inverted filter can't be held by the registers
+        assert_eq!(registers("100/700, 200, 7E8 ext"), ((0, 0x7FF), (0x7E8, 0)));
+        assert_eq!(registers("100/700, 18FF0000/1FFF0000 ext inv"), open);
+        // The registers pass at least the frames of the software filter
+        for content in [
+            "120/7F0",
+            "120/7F0, 18FF0000/1FFF0000 ext",
+            "100, 200",
+            "7FF ext inv",
+        ] {
+            let filters = AcceptanceFilter::parse(content).unwrap();
+            let (standard, extended) = AcceptanceFilter::pcan_code_masks(&filters);
+            for id in [
+                0x0, 0x100, 0x120, 0x12F, 0x200, 0x7FF, 0x18FF0000, 0x18FE0100,
+            ] {
+                for is_extended in [false, true] {
+                    if !is_extended && id > STANDARD_ID_MAX {
+                        continue;
+                    }
+                    let (code, mask) = if is_extended { extended } else { standard };
+                    if AcceptanceFilter::accepts(&filters, id, is_extended) {
+                        assert_eq!((id ^ code) & !mask, 0, "{} {:X}", content, id);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn accepts_any_matching_filter() {
+        let filters = AcceptanceFilter::parse("123, 18FF0000/1FFF0000 ext").unwrap();
+        assert!(AcceptanceFilter::accepts(&filters, 0x123, false));
+        assert!(AcceptanceFilter::accepts(&filters, 0x18FF0001, true));
+        assert!(!AcceptanceFilter::accepts(&filters, 0x124, false));
+        assert!(!AcceptanceFilter::accepts(&filters, 0x123, true));
+        assert!(AcceptanceFilter::accepts(&[], 0x124, true));
+    }
+}
@@ -0,0 +1,250 @@
+use slint::{SharedString, Weak};
+#[cfg(target_os = "linux")]
+use socketcan::{CanInterface, CanSocket, Frame, Socket, SocketOptions};
+use std::{
+    fmt::Write,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use crate::slint_generatedAppWindow::AppWindow;
+
+use super::backend::{is_backend, open_backend, BackendEvent};
+use super::can_handler::BITRATES;
+
+const DWELL_TIME: Duration = Duration::from_millis(500);
+const MIN_FRAMES: u32 = 3;
+
+pub struct ProbeResult {
+    pub bitrate: u32,
+    pub frames: u32,
+    pub errors: u32,
+}
+
+impl ProbeResult {
+    fn score(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        let frames = self.frames as f64;
+        frames * frames / (frames + self.errors as f64)
+    }
+}
+
+pub trait BitrateProbe {
+    fn probe(&mut self, bitrate: u32, dwell_time: Duration) -> Result<ProbeResult, String>;
+}
+
+pub struct Detection {
+    pub bitrate: u32,
+    pub confidence: f64,
+}
+
+pub fn detect(results: &[ProbeResult]) -> Option<Detection> {
+    let best = results
+        .iter()
+        .filter(|result| result.frames >= MIN_FRAMES)
+        .max_by(|a, b| a.score().total_cmp(&b.score()))?;
+    let total: f64 = results.iter().map(ProbeResult::score).sum();
+    let error_free = best.frames as f64 / (best.frames + best.errors) as f64;
+    Some(Detection {
+        bitrate: best.bitrate,
+        confidence: error_free * best.score() / total,
+    })
+}
+
+#[cfg(target_os = "linux")]
+pub struct SocketCanProbe<'a> {
+    pub iface: &'a str,
+}
+
+#[cfg(target_os = "linux")]
+impl<'a> BitrateProbe for SocketCanProbe<'a> {
+    fn probe(&mut self, bitrate: u32, dwell_time: Duration) -> Result<ProbeResult, String> {
+        use socketcan::nl::CanCtrlMode;
+        let can_if = CanInterface::open(self.iface).map_err(|e| e.to_string())?;
+        let _ = can_if.bring_down();
+        can_if
+            .set_bitrate(bitrate, None)
+            .map_err(|e| e.to_string())?;
+        can_if
+            .set_ctrlmode(CanCtrlMode::ListenOnly, true)
+            .map_err(|e| e.to_string())?;
+        let _ = can_if.set_ctrlmode(CanCtrlMode::BerrReporting, true);
+        can_if.bring_up().map_err(|e| e.to_string())?;
+        let socket = CanSocket::open(self.iface).map_err(|e| e.to_string())?;
+        let _ = socket.set_nonblocking(true);
+        socket
+            .set_error_filter_accept_all()
+            .map_err(|e| e.to_string())?;
+        let mut result = ProbeResult {
+            bitrate,
+            frames: 0,
+            errors: 0,
+        };
+        let start = Instant::now();
+        while start.elapsed() < dwell_time {
+            match socket.read_frame() {
+                Ok(frame) if frame.is_error_frame() => result.errors += 1,
+                Ok(_) => result.frames += 1,
+                Err(_) => sleep(Duration::from_millis(1)),
+            }
+        }
+        let _ = can_if.bring_down();
+        Ok(result)
+    }
+}
+
+pub struct BackendProbe<'a> {
+    pub name: &'a str,
+}
+
+impl<'a> BitrateProbe for BackendProbe<'a> {
+    fn probe(&mut self, bitrate: u32, dwell_time: Duration) -> Result<ProbeResult, String> {
+        let mut backend = open_backend(self.name, bitrate, true)?;
+        let mut result = ProbeResult {
+            bitrate,
+            frames: 0,
+            errors: 0,
+        };
+        let start = Instant::now();
+        while start.elapsed() < dwell_time {
+            match backend.read_event() {
+                Ok(Some(BackendEvent::Frame(_))) => result.frames += 1,
+                Ok(Some(BackendEvent::Error(_))) => result.errors += 1,
+                Ok(None) => sleep(Duration::from_millis(1)),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Ok(result)
+    }
+}
+
+pub struct AutoBaud<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+}
+
+impl<'a> AutoBaud<'a> {
+    pub fn run(&self, name: &str) {
+        if is_backend(name) {
+            self.detect(&mut BackendProbe { name });
+            return;
+        }
+        #[cfg(target_os = "linux")]
+        self.detect(&mut SocketCanProbe { iface: name });
+        // PCAN channels can't be opened in listen-only mode, a wrong bitrate would disturb the bus
+        #[cfg(target_os = "windows")]
+        self.finish(
+            None,
+            "Auto-baud is not supported for PCAN interfaces".to_string(),
+        );
+    }
+
+    fn detect(&self, probe: &mut dyn BitrateProbe) {
+        let mut results = Vec::default();
+        let mut report = String::new();
+        for (bitrate_name, bitrate) in BITRATES {
+            self.status(format!("Auto-baud: listening at {} ...", bitrate_name));
+            match probe.probe(bitrate, DWELL_TIME) {
+                Ok(result) => {
+                    if result.frames > 0 || result.errors > 0 {
+                        let _ = writeln!(
+                            report,
+                            "{}: {} frames, {} errors",
+                            bitrate_name, result.frames, result.errors
+                        );
+                    }
+                    results.push(result);
+                }
+                // e.g. bitrates which are not supported by slcan
+                Err(e) => println!("Auto-baud: {} not tested - {}", bitrate_name, e),
+            }
+        }
+        match detect(&results) {
+            Some(detection) => {
+                let bitrate_name = BITRATES
+                    .iter()
+                    .find(|(_, bitrate)| *bitrate == detection.bitrate)
+                    .map(|(name, _)| *name)
+                    .unwrap_or_default();
+                self.finish(
+                    Some(bitrate_name),
+                    format!(
+                        "Detected {} (confidence {:.0}%)\n{}",
+                        bitrate_name,
+                        detection.confidence * 100.0,
+                        report
+                    ),
+                );
+            }
+            None if report.is_empty() => {
+                self.finish(None, "Auto-baud: no traffic on the bus".to_string())
+            }
+            None => self.finish(None, format!("Auto-baud: no bitrate detected\n{}", report)),
+        }
+    }
+
+    fn status(&self, status: String) {
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.set_init_string(SharedString::from(status));
+        });
+    }
+
+    fn finish(&self, bitrate: Option<&'static str>, status: String) {
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            if let Some(bitrate) = bitrate {
+                ui.set_init_bitrate(SharedString::from(bitrate));
+            }
+            ui.set_init_string(SharedString::from(status));
+            ui.set_is_auto_baud(false);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe_all(bus: &str, bitrates: &[u32]) -> Vec<ProbeResult> {
+        let mut probe = BackendProbe { name: bus };
+        bitrates
+            .iter()
+            .map(|bitrate| probe.probe(*bitrate, Duration::from_millis(100)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn detects_simulated_bitrate() {
+        let results = probe_all("sim://250000/2000", &[500_000, 250_000, 125_000]);
+        // Mostly errors at the wrong bitrates
+        assert!(results[0].errors > results[0].frames);
+        assert_eq!(results[1].errors, 0);
+        let detection = detect(&results).unwrap();
+        assert_eq!(detection.bitrate, 250_000);
+        assert!(detection.confidence > 0.9, "{}", detection.confidence);
+    }
+
+    #[test]
+    fn errors_lower_confidence() {
+        let result = |bitrate, frames, errors| ProbeResult {
+            bitrate,
+            frames,
+            errors,
+        };
+        let clean = detect(&[result(500_000, 100, 0), result(250_000, 0, 80)]).unwrap();
+        let noisy = detect(&[result(500_000, 100, 40), result(250_000, 0, 80)]).unwrap();
+        assert_eq!((clean.bitrate, noisy.bitrate), (500_000, 500_000));
+        assert_eq!(clean.confidence, 1.0);
+        assert!(noisy.confidence < clean.confidence);
+        // Corrupted frames at a wrong bitrate share the valid traffic
+        let shared = detect(&[result(500_000, 100, 0), result(250_000, 10, 90)]).unwrap();
+        assert!(shared.confidence < clean.confidence);
+    }
+
+    #[test]
+    fn needs_traffic() {
+        assert!(detect(&[]).is_none());
+        let results = probe_all("sim://250000/10", &[500_000]);
+        assert!(detect(&results).is_none());
+    }
+}
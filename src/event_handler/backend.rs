@@ -0,0 +1,121 @@
+use std::{io, time::Duration};
+
+use super::cannelloni::{CannelloniSocket, CANNELLONI_PREFIX};
+use super::simulated::{SimulatedBus, SIMULATED_PREFIX};
+use super::slcan::{SlcanPort, SLCAN_PREFIX};
+use super::socketcand::{SocketcandClient, SOCKETCAND_PREFIX};
+
+pub struct RawFrame {
+    pub id: u32,
+    pub is_extended: bool,
+    pub is_remote: bool,
+    pub is_fd: bool,
+    pub brs: bool,
+    pub data: Vec<u8>,
+    pub timestamp: Option<Duration>,
+}
+
+pub enum BackendEvent {
+    Frame(RawFrame),
+    Error(String),
+}
+
+pub trait CanBackend {
+    fn read_event(&mut self) -> io::Result<Option<BackendEvent>>;
+    fn write_frame(&mut self, is_extended: bool, id: u32, data: &[u8]) -> io::Result<()>;
+}
+
+pub fn is_backend(name: &str) -> bool {
+    [
+        SLCAN_PREFIX,
+        SOCKETCAND_PREFIX,
+        CANNELLONI_PREFIX,
+        SIMULATED_PREFIX,
+    ]
+    .iter()
+    .any(|prefix| name.starts_with(prefix))
+}
+
+pub fn check_backend(name: &str) -> Result<(), String> {
+    if let Some(serial_port) = name.strip_prefix(SLCAN_PREFIX) {
+        SlcanPort::split_port(serial_port).map(|_| ())
+    } else if let Some(address) = name.strip_prefix(SOCKETCAND_PREFIX) {
+        SocketcandClient::parse_address(address).map(|_| ())
+    } else if let Some(address) = name.strip_prefix(CANNELLONI_PREFIX) {
+        CannelloniSocket::parse_address(address).map(|_| ())
+    } else if let Some(bus) = name.strip_prefix(SIMULATED_PREFIX) {
+        SimulatedBus::parse(bus).map(|_| ())
+    } else {
+        Err(format!("Unknown backend {}", name))
+    }
+}
+
+pub fn host_address(host: &str, default_port: u16) -> Result<String, String> {
+    let (name, port) = match host.rsplit_once(':') {
+        Some((name, port)) => (
+            name,
+            port.parse::<u16>()
+                .map_err(|_| format!("Invalid port {}", port))?,
+        ),
+        None => (host, default_port),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid host {}", host));
+    }
+    Ok(format!("{}:{}", name, port))
+}
+
+pub fn open_backend(
+    name: &str,
+    bitrate: u32,
+    listen_only: bool,
+) -> Result<Box<dyn CanBackend>, String> {
+    if let Some(serial_port) = name.strip_prefix(SLCAN_PREFIX) {
+        Ok(Box::new(SlcanPort::open(
+            serial_port,
+            bitrate,
+            listen_only,
+            true,
+        )?))
+    } else if let Some(address) = name.strip_prefix(SOCKETCAND_PREFIX) {
+        Ok(Box::new(SocketcandClient::open(address)?))
+    } else if let Some(address) = name.strip_prefix(CANNELLONI_PREFIX) {
+        Ok(Box::new(CannelloniSocket::open(address)?))
+    } else if let Some(bus_bitrate) = name.strip_prefix(SIMULATED_PREFIX) {
+        Ok(Box::new(SimulatedBus::open(bus_bitrate, bitrate)?))
+    } else {
+        Err(format!("Unknown backend {}", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_backend_addresses() {
+        assert!(check_backend("slcan:/dev/ttyUSB0").is_ok());
+        assert!(check_backend("slcan:/dev/ttyUSB0#031C").is_ok());
+        assert!(check_backend("slcan:/dev/ttyUSB0#31C").is_err());
+        assert!(check_backend("slcan:").is_err());
+        assert!(check_backend("socketcand://192.168.1.10").is_ok());
+        assert!(check_backend("socketcand://192.168.1.10:29536/can1").is_ok());
+        assert!(check_backend("socketcand://192.168.1.10:port").is_err());
+        assert!(check_backend("socketcand://host/").is_err());
+        assert!(check_backend("socketcand://").is_err());
+        assert!(check_backend("cannelloni://10.0.0.2:20000/20001").is_ok());
+        assert!(check_backend("cannelloni://10.0.0.2/local").is_err());
+        assert!(check_backend("sim://500000/1000").is_ok());
+        assert!(check_backend("sim://fast").is_err());
+        assert!(check_backend("sim://500000/0").is_err());
+        assert!(check_backend("can0").is_err());
+    }
+
+    #[test]
+    fn adds_default_port() {
+        assert_eq!(host_address("localhost", 20000).unwrap(), "localhost:20000");
+        assert_eq!(host_address("localhost:1", 20000).unwrap(), "localhost:1");
+        assert!(host_address(":1", 20000).is_err());
+        assert!(host_address("local host", 20000).is_err());
+    }
+}
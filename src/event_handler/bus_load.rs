@@ -0,0 +1,307 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+const HISTORY_LEN: usize = 100;
+const SMOOTHING: f64 = 0.2;
+
+const CRC15_POLYNOMIAL: u16 = 0x4599;
+/// CRC delimiter, ACK slot, ACK delimiter, end of frame and interframe space
+const FRAME_TAIL_BITS: u32 = 1 + 1 + 1 + 7 + 3;
+
+#[derive(Clone, Copy)]
+pub enum Stuffing {
+    /// Count the stuff bits of the actual frame content
+    Exact,
+    /// Assume a stuff bit after every 4 bits (alternating 5 bit sequences)
+    WorstCase,
+}
+
+pub fn frame_bits(
+    id: u32,
+    is_extended: bool,
+    is_remote: bool,
+    data: &[u8],
+    stuffing: Stuffing,
+) -> u32 {
+    let data = if is_remote { &[][..] } else { data };
+    let stuffed_bits = match stuffing {
+        Stuffing::Exact => {
+            let bits = stuffed_region(id, is_extended, is_remote, data);
+            bits.len() as u32 + stuff_bits(&bits)
+        }
+        Stuffing::WorstCase => {
+            // SOF, arbitration, control, data and CRC fields
+            let len = if is_extended { 54 } else { 34 } + 8 * data.len() as u32;
+            len + (len - 1) / 4
+        }
+    };
+    stuffed_bits + FRAME_TAIL_BITS
+}
+
+/// Duration of a CAN FD frame with worst case stuffing, the data phase uses `data_bitrate`
+/// if the bitrate is switched (BRS).
+pub fn fd_frame_time(
+    is_extended: bool,
+    brs: bool,
+    data_len: usize,
+    nominal_bitrate: u32,
+    data_bitrate: u32,
+) -> Duration {
+    // SOF, ID, RRS/SRR, IDE, FDF, res, BRS
+    let arbitration = if is_extended { 36 } else { 17 };
+    let arbitration = arbitration + (arbitration - 1) / 4;
+    // ESI, DLC and data are stuffed dynamically, stuff count and CRC have fixed stuff bits
+    let dynamic = 1 + 4 + 8 * data_len as u32;
+    let crc_len = if data_len > 16 { 21 } else { 17 };
+    let data_phase = dynamic + dynamic / 4 + 4 + crc_len + 1 + (4 + crc_len) / 4;
+    let data_bitrate = if brs { data_bitrate } else { nominal_bitrate };
+    let nominal_bits = (arbitration + FRAME_TAIL_BITS) as f64;
+    Duration::from_secs_f64(
+        nominal_bits / nominal_bitrate as f64 + data_phase as f64 / data_bitrate as f64,
+    )
+}
+
+/// Bits from SOF to the end of the CRC sequence, the part of a classic frame which is stuffed.
+fn stuffed_region(id: u32, is_extended: bool, is_remote: bool, data: &[u8]) -> Vec<bool> {
+    let mut bits = Vec::with_capacity(54 + 8 * data.len() + 15);
+    let mut push = |value: u32, len: u32| {
+        for bit in (0..len).rev() {
+            bits.push(value & (1 << bit) != 0);
+        }
+    };
+    push(0, 1); // SOF
+    if is_extended {
+        push(id >> 18, 11); // Base ID
+        push(1, 1); // SRR
+        push(1, 1); // IDE
+        push(id, 18); // ID extension
+        push(is_remote as u32, 1); // RTR
+        push(0, 2); // r1, r0
+    } else {
+        push(id, 11);
+        push(is_remote as u32, 1); // RTR
+        push(0, 2); // IDE, r0
+    }
+    push(data.len() as u32, 4); // DLC
+    for byte in data {
+        push(*byte as u32, 8);
+    }
+    let crc = crc15(&bits);
+    for bit in (0..15).rev() {
+        bits.push(crc & (1 << bit) != 0);
+    }
+    bits
+}
+
+/// Number of stuff bits inserted after every 5 consecutive bits of the same level.
+fn stuff_bits(bits: &[bool]) -> u32 {
+    let mut stuff_bits = 0;
+    let mut last = None;
+    let mut count = 0;
+    for &bit in bits {
+        if Some(bit) == last {
+            count += 1;
+        } else {
+            last = Some(bit);
+            count = 1;
+        }
+        if count == 5 {
+            // The stuff bit has the opposite level and starts a new sequence
+            stuff_bits += 1;
+            last = Some(!bit);
+            count = 1;
+        }
+    }
+    stuff_bits
+}
+
+fn crc15(bits: &[bool]) -> u16 {
+    let mut crc: u16 = 0;
+    for &bit in bits {
+        let crc_next = bit ^ (crc & 0x4000 != 0);
+        crc = (crc << 1) & 0x7FFF;
+        if crc_next {
+            crc ^= CRC15_POLYNOMIAL;
+        }
+    }
+    crc
+}
+
+pub struct BusLoadSample {
+    pub smoothed: f64,
+    pub peak: f64,
+    pub worst_case: f64,
+    pub plot: String,
+}
+
+pub struct BusLoad {
+    nominal_bitrate: u32,
+    data_bitrate: u32,
+    busy: Duration,
+    busy_worst_case: Duration,
+    sample_start: Instant,
+    smoothed: f64,
+    peak: f64,
+    history: VecDeque<f64>,
+}
+
+impl BusLoad {
+    pub fn new(nominal_bitrate: u32, data_bitrate: u32) -> Self {
+        Self {
+            nominal_bitrate,
+            data_bitrate,
+            busy: Duration::ZERO,
+            busy_worst_case: Duration::ZERO,
+            sample_start: Instant::now(),
+            smoothed: 0.0,
+            peak: 0.0,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        }
+    }
+
+    pub fn add_frame(
+        &mut self,
+        id: u32,
+        is_extended: bool,
+        is_remote: bool,
+        data: &[u8],
+    ) -> Duration {
+        let bit_time = 1.0 / self.nominal_bitrate as f64;
+        let bits = frame_bits(id, is_extended, is_remote, data, Stuffing::Exact);
+        let frame_time = Duration::from_secs_f64(bits as f64 * bit_time);
+        self.busy += frame_time;
+        let bits = frame_bits(id, is_extended, is_remote, data, Stuffing::WorstCase);
+        self.busy_worst_case += Duration::from_secs_f64(bits as f64 * bit_time);
+        frame_time
+    }
+
+    pub fn add_fd_frame(&mut self, is_extended: bool, brs: bool, data_len: usize) -> Duration {
+        let frame_time = fd_frame_time(
+            is_extended,
+            brs,
+            data_len,
+            self.nominal_bitrate,
+            self.data_bitrate,
+        );
+        self.busy += frame_time;
+        self.busy_worst_case += frame_time;
+        frame_time
+    }
+
+    pub fn sample(&mut self) -> Option<BusLoadSample> {
+        let elapsed = self.sample_start.elapsed();
+        if elapsed < SAMPLE_INTERVAL {
+            return None;
+        }
+        let load = (self.busy.as_secs_f64() / elapsed.as_secs_f64() * 100.0).min(100.0);
+        let worst_case =
+            (self.busy_worst_case.as_secs_f64() / elapsed.as_secs_f64() * 100.0).min(100.0);
+        self.busy = Duration::ZERO;
+        self.busy_worst_case = Duration::ZERO;
+        self.sample_start = Instant::now();
+        self.smoothed = if self.history.is_empty() {
+            load
+        } else {
+            self.smoothed + SMOOTHING * (load - self.smoothed)
+        };
+        self.peak = self.peak.max(load);
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.smoothed);
+        Some(BusLoadSample {
+            smoothed: self.smoothed,
+            peak: self.peak,
+            worst_case,
+            plot: self.plot_path(),
+        })
+    }
+
+    fn plot_path(&self) -> String {
+        let mut path = String::new();
+        for (index, load) in self.history.iter().enumerate() {
+            let x = index as f64 * 200.0 / (HISTORY_LEN - 1) as f64;
+            let y = 100.0 - load;
+            let command = if index == 0 { 'M' } else { 'L' };
+            let _ = write!(path, "{} {:.1} {:.1} ", command, x, y);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes_to_bits(bytes: &[u8]) -> Vec<bool> {
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |bit| byte & (1 << bit) != 0))
+            .collect()
+    }
+
+    #[test]
+    fn computes_crc15() {
+        // Check value of CRC-15/CAN
+        assert_eq!(crc15(&bytes_to_bits(b"123456789")), 0x059E);
+        assert_eq!(crc15(&[false; 19]), 0);
+    }
+
+    #[test]
+    fn counts_worst_case_bits() {
+        assert_eq!(
+            frame_bits(0x7FF, false, false, &[0; 8], Stuffing::WorstCase),
+            135
+        );
+        assert_eq!(
+            frame_bits(0x1FFFFFFF, true, false, &[0; 8], Stuffing::WorstCase),
+            160
+        );
+        // Remote frames carry no data
+        assert_eq!(
+            frame_bits(0x7FF, false, true, &[0; 8], Stuffing::WorstCase),
+            55
+        );
+    }
+
+    #[test]
+    fn counts_exact_stuff_bits() {
+        // 34 dominant bits up to the CRC (which is 0) get a stuff bit after every 5
+        let bits = stuffed_region(0, false, false, &[]);
+        assert_eq!((bits.len(), stuff_bits(&bits)), (34, 6));
+        assert_eq!(frame_bits(0, false, false, &[], Stuffing::Exact), 53);
+        assert_eq!(
+            frame_bits(
+                0x123,
+                false,
+                false,
+                &[0xDE, 0xAD, 0xBE, 0xEF],
+                Stuffing::Exact
+            ),
+            81
+        );
+        let data = [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88];
+        let bits = stuffed_region(0x18FF1234, true, false, &data);
+        assert_eq!((bits.len(), stuff_bits(&bits)), (118, 5));
+        assert_eq!(
+            frame_bits(0x18FF1234, true, false, &data, Stuffing::Exact),
+            136
+        );
+    }
+
+    #[test]
+    fn switches_fd_data_bitrate() {
+        // 34 nominal bits, 678 data phase bits
+        let brs = fd_frame_time(false, true, 64, 500_000, 2_000_000);
+        assert_eq!(brs, Duration::from_micros(68 + 339));
+        let no_brs = fd_frame_time(false, false, 64, 500_000, 2_000_000);
+        assert_eq!(no_brs, Duration::from_micros(68 + 1356));
+        // Extended arbitration, 17 bit CRC up to 16 bytes: 57 nominal and 113 data phase bits
+        let short = fd_frame_time(true, true, 8, 500_000, 2_000_000);
+        assert!(short.abs_diff(Duration::from_nanos(170_500)) < Duration::from_nanos(10));
+    }
+}
@@ -1,72 +1,218 @@
-use can_dbc::DBC;
-use chrono::Utc;
+use chrono::{DateTime, Local};
 #[cfg(target_os = "windows")]
 use pcan_basic::{
     bus::UsbBus,
-    socket::{usb::UsbCanSocket, CanFrame},
+    socket::{usb::UsbCanSocket, CanFrame, MessageType},
 };
 use slint::{Model, ModelRc, SharedString, VecModel, Weak};
 #[cfg(target_os = "linux")]
-use socketcan::{CanFrame, CanInterface, CanSocket, EmbeddedFrame, Frame, Socket};
+use socketcan::{
+    CanFrame, CanInterface, CanSocket, EmbeddedFrame, ExtendedId, Frame, Socket, SocketOptions,
+    StandardId,
+};
 use std::{
     collections::HashMap,
-    fmt::Write,
+    fmt::{Display, Write},
     rc::Rc,
-    sync::{mpsc::Receiver, mpsc::Sender, Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        mpsc::{Receiver, Sender},
+        Arc, Mutex,
+    },
     thread::sleep,
     time::{Duration, Instant},
 };
 
-use crate::slint_generatedAppWindow::{AppWindow, CanData, CanSignal};
+use crate::slint_generatedAppWindow::{
+    AppWindow, CanData, CanSignal, ChannelOptions, CycleAlarm, StateTransition,
+};
+
+pub const BITRATES: [(&str, u32); 14] = [
+    ("1 Mbit/s", 1_000_000),
+    ("800 kbit/s", 800_000),
+    ("500 kbit/s", 500_000),
+    ("250 kbit/s", 250_000),
+    ("125 kbit/s", 125_000),
+    ("100 kbit/s", 100_000),
+    ("95.238 kbit/s", 95_238),
+    ("83.333 kbit/s", 83_333),
+    ("50 kbit/s", 50_000),
+    ("47.619 kbit/s", 47_619),
+    ("33.333 kbit/s", 33_333),
+    ("20 kbit/s", 20_000),
+    ("10 kbit/s", 10_000),
+    ("5 kbit/s", 5_000),
+];
+
+pub type TransmitFrame = (bool, u32, Vec<u8>);
+
 pub struct CanHandler<'a> {
+    pub channel: usize,
     #[cfg(target_os = "linux")]
     pub iface: &'a str,
     #[cfg(target_os = "windows")]
-    pub iface: UsbBus,
+    pub iface: Option<UsbBus>,
+    pub backend: Option<String>,
+    pub options: ChannelOptions,
     pub ui_handle: &'a Weak<AppWindow>,
-    pub mspc_rx: &'a Arc<Mutex<Receiver<DBC>>>,
-    pub can_tx: Sender<CanFrame>,
+    pub can_tx: Sender<TraceEntry>,
+    pub recording: bool,
+    pub obd_tx: Sender<(u32, Vec<u8>)>,
+    pub transmit_rx: Receiver<TransmitFrame>,
+    pub xcp_tx: Sender<(u32, Vec<u8>)>,
+    pub xcp_dto_id: Arc<AtomicU32>,
+    pub gateway_tx: Sender<(usize, TransmitFrame)>,
+    pub statistics_tx: Sender<ObservedFrame>,
+    pub gateway_enabled: Arc<AtomicBool>,
+    pub n2k_rx: Receiver<Vec<PgnDefinition>>,
+    pub n2k: Option<N2kDecoder>,
     pub bitrate: String,
-    pub dbc: Option<DBC>,
+    pub data_bitrate: String,
+    pub dbc: Option<Arc<DbcTable>>,
+    pub unknown_ids: UnknownIds,
+    pub cycle_monitor: Option<CycleMonitor>,
+    pub cycle_timeout: Arc<AtomicU32>,
+    pub time_format: Arc<Mutex<TimeFormat>>,
+    pub command_rx: Receiver<ChannelCommand>,
+    pub acceptance_filters: Arc<Mutex<Vec<AcceptanceFilter>>>,
+    pub capture_tx: Sender<Capture>,
+    pub capture_enabled: Arc<AtomicBool>,
+    pub signal_updates: SignalUpdates,
+    pub n2k_updates: SignalUpdates,
+    pub last_refresh: Instant,
+}
+
+pub enum ChannelCommand {
+    Reconnect,
+    Disconnect,
+    ApplyFilters,
+    LoadDbc(Arc<DbcTable>),
+    UnloadDbc,
+    SetRecording(bool),
+    SetBitrate(String),
 }
 
-static mut NEW_DBC_CHECK: bool = false;
+const MAX_ALARMS: usize = 100;
+const MAX_STATE_HISTORY: usize = 100;
+#[cfg(target_os = "linux")]
+const CAN_ID_MASK: u32 = 0x1FFFFFFF;
+use super::acceptance_filter::AcceptanceFilter;
+use super::backend::{open_backend, BackendEvent};
+use super::bus_load::{BusLoad, BusLoadSample};
+use super::cycle_monitor::{cycle_state, CycleMonitor, CycleTimeout, CYCLE_MISSING};
+use super::dbc_table::DbcTable;
+#[cfg(target_os = "linux")]
+use super::error_frame::ErrorFrame;
+use super::n2k::{N2kDecoder, PgnDefinition};
+use super::obd::OBD_RESPONSE_IDS;
 #[cfg(target_os = "windows")]
 use super::p_can_bitrate;
+use super::signal_updates::{MessageUpdate, SignalUpdates, UI_REFRESH_INTERVAL};
+use super::statistics::ObservedFrame;
+use super::time_format::TimeFormat;
+#[cfg(target_os = "linux")]
+use super::timestamp::kernel_time;
+#[cfg(target_os = "windows")]
+use super::timestamp::pcan_timestamp;
+use super::timestamp::ReceiveClock;
+use super::trace::TraceEntry;
+use super::trigger::{Capture, CaptureEvent};
+use super::unknown_ids::UnknownIds;
 use super::{EVEN_COLOR, ODD_COLOR};
 
 impl<'a> CanHandler<'a> {
-    pub fn process_can_messages(&mut self) {
+    pub fn process_can_messages(&mut self) -> bool {
+        while let Ok(command) = self.command_rx.try_recv() {
+            if let Some(false) = self.handle_command(command) {
+                return false;
+            }
+        }
+        if let Some(backend) = self.backend.clone() {
+            return self.process_backend(&backend);
+        }
         #[cfg(target_os = "linux")]
         {
-            let can_if = CanInterface::open(self.iface).unwrap();
+            let can_if = match CanInterface::open(self.iface) {
+                Ok(can_if) => can_if,
+                Err(e) => {
+                    println!(
+                        "ERR: Failed to open {} - {:?}\nTry to re-connect...",
+                        self.iface, e
+                    );
+                    self.update_channel_state(SharedString::from("NOT FOUND"), None, None);
+                    sleep(Duration::from_millis(1000));
+                    return true;
+                }
+            };
             let _ = can_if.bring_down();
             let _ = can_if.set_bitrate(self.bitrate().unwrap(), None);
+            self.set_ctrlmode(&can_if);
             let _ = can_if.bring_up();
-            let tx_can_socket = self.open_can_socket();
-            let rx_can_socket = self.open_can_socket();
-            self.process_ui_events(tx_can_socket, rx_can_socket, can_if);
+            match self.open_can_socket() {
+                Ok(can_socket) => self.process_ui_events(can_socket, can_if),
+                Err(keep_running) => keep_running,
+            }
         }
         #[cfg(target_os = "windows")]
         {
+            let Some(iface) = self.iface else {
+                return false;
+            };
             let baudrate = p_can_bitrate(&self.bitrate).unwrap();
-            match UsbCanSocket::open(self.iface, baudrate) {
-                Ok(socket) => {
-                    self.process_ui_events(socket);
-                }
+            match UsbCanSocket::open(iface, baudrate) {
+                Ok(socket) => self.process_ui_events(socket),
                 Err(e) => {
                     println!("Failed to open CAN socket: {:?}", e);
+                    sleep(Duration::from_millis(1000));
+                    true
+                }
+            }
+        }
+    }
+    #[cfg(target_os = "linux")]
+    fn set_ctrlmode(&self, can_if: &CanInterface) {
+        use socketcan::nl::CanCtrlMode;
+        let modes = [
+            (CanCtrlMode::ListenOnly, self.options.listen_only),
+            (CanCtrlMode::Loopback, self.options.loopback),
+            (CanCtrlMode::OneShot, self.options.one_shot),
+            (CanCtrlMode::TripleSampling, self.options.triple_sampling),
+        ];
+        for (mode, on) in modes {
+            if let Err(e) = can_if.set_ctrlmode(mode, on) {
+                if on {
+                    println!("ERR: {} does not support {:?} - {}", self.iface, mode, e);
                 }
             }
         }
+        // Bus errors are reported as error frames
+        let _ = can_if.set_ctrlmode(CanCtrlMode::BerrReporting, true);
+        let restart_ms = self.options.restart_ms.max(0) as u32;
+        if let Err(e) = can_if.set_restart_ms(restart_ms) {
+            println!("ERR: Failed to set restart-ms of {} - {}", self.iface, e);
+        }
     }
+
     #[cfg(target_os = "linux")]
-    fn open_can_socket(&self) -> CanSocket {
+    fn open_can_socket(&mut self) -> Result<CanSocket, bool> {
         loop {
+            while let Ok(command) = self.command_rx.try_recv() {
+                if let Some(keep_running) = self.handle_command(command) {
+                    return Err(keep_running);
+                }
+            }
             match CanSocket::open(self.iface) {
                 Ok(socket) => {
                     let _ = socket.set_nonblocking(true);
-                    break socket;
+                    // Error frames are shown in the trace and carry the error counters
+                    if let Err(e) = socket.set_error_filter_accept_all() {
+                        println!(
+                            "ERR: Failed to enable error frames on {} - {}",
+                            self.iface, e
+                        );
+                    }
+                    self.set_socket_filters(&socket);
+                    break Ok(socket);
                 }
                 Err(e) => {
                     println!(
@@ -79,146 +225,316 @@ impl<'a> CanHandler<'a> {
         }
     }
     #[cfg(target_os = "linux")]
-    fn process_ui_events(
-        &mut self,
-        tx_can_socket: CanSocket,
-        rx_can_socket: CanSocket,
-        can_if: CanInterface,
-    ) {
-        use socketcan::{ExtendedId, StandardId};
+    fn set_socket_filters(&self, socket: &CanSocket) {
+        let filters: Vec<_> = self
+            .acceptance_filters
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|filter| filter.to_can_filter())
+            .collect();
+        let result = if filters.is_empty() {
+            socket.set_filter_accept_all()
+        } else {
+            socket.set_filters(&filters)
+        };
+        if let Err(e) = result {
+            println!("ERR: Failed to set the filters of {} - {}", self.iface, e);
+        }
+    }
 
-        let mut start_bus_load = Instant::now();
-        let mut total_bits = 0;
-        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-            ui.on_can_transmit(move |is_extended, can_id, can_data| {
-                match Self::convert_hex_string_u32(&can_id) {
-                    Ok(id) => match Self::convert_hex_string_arr(&can_data) {
-                        Ok(data) => {
-                            if is_extended {
-                                match ExtendedId::new(id) {
-                                    Some(id) => {
-                                        let can_frame = CanFrame::new(id, &data).unwrap();
-                                        let _ = tx_can_socket.write_frame(&can_frame);
-                                    }
-                                    None => {
-                                        println!("Invalid CAN extended ID {}", id)
-                                    }
-                                }
-                            } else {
-                                match StandardId::new(id as u16) {
-                                    Some(id) => {
-                                        let can_frame = CanFrame::new(id, &data).unwrap();
-                                        let _ = tx_can_socket.write_frame(&can_frame);
-                                    }
-                                    None => {
-                                        println!("Invalid CAN standard ID {}", id)
-                                    }
-                                }
-                            };
-                        }
-                        Err(e) => {
-                            println!("Failed to parse can data {}, error {}", can_data, e);
+    #[cfg(target_os = "linux")]
+    fn process_ui_events(&mut self, can_socket: CanSocket, can_if: CanInterface) -> bool {
+        // Virtual interfaces (vcan) have neither a state nor a bitrate
+        let bitrate = can_if
+            .bit_rate()
+            .ok()
+            .flatten()
+            .unwrap_or(self.bitrate().unwrap());
+        let mut bus_load = BusLoad::new(bitrate, self.data_bitrate().unwrap());
+        let mut error_counters = None;
+        loop {
+            if self.is_refresh_due() {
+                let bus_state = match can_if.state().ok().flatten() {
+                    Some(socketcan::nl::CanState::ErrorActive) => "ERR_ACTIVE",
+                    Some(socketcan::nl::CanState::ErrorWarning) => "ERR_WARNING",
+                    Some(socketcan::nl::CanState::ErrorPassive) => "ERR_PASSIVE",
+                    Some(socketcan::nl::CanState::BusOff) => "BUSOFF",
+                    Some(socketcan::nl::CanState::Stopped) => "STOPPED",
+                    Some(socketcan::nl::CanState::Sleeping) => "SLEEPING",
+                    None => "OK",
+                };
+                self.update_channel_state(bus_state.into(), bus_load.sample(), error_counters);
+                self.update_signals();
+            }
+            self.update_decoders();
+            while let Ok(command) = self.command_rx.try_recv() {
+                match command {
+                    ChannelCommand::ApplyFilters => self.set_socket_filters(&can_socket),
+                    command => {
+                        if let Some(keep_running) = self.handle_command(command) {
+                            return keep_running;
                         }
-                    },
-                    Err(e) => {
-                        println!("Failed to parse can id {}, error: {}", can_id, e);
                     }
                 }
-            });
-        });
-
-        loop {
-            let bus_state = match can_if.state().unwrap().unwrap() {
-                socketcan::nl::CanState::ErrorActive => "ERR_ACTIVE",
-                socketcan::nl::CanState::ErrorWarning => "ERR_WARNING",
-                socketcan::nl::CanState::ErrorPassive => "ERR_PASSIVE",
-                socketcan::nl::CanState::BusOff => "BUSOFF",
-                socketcan::nl::CanState::Stopped => "STOPPED",
-                socketcan::nl::CanState::Sleeping => "SLEEPING",
-            };
-            let bitrate = can_if.bit_rate().unwrap().unwrap();
-            let busload = if start_bus_load.elapsed() >= Duration::from_millis(1000) {
-                start_bus_load = Instant::now();
-                let bus_load = (total_bits as f64 / bitrate as f64) * 100.0;
-                total_bits = 0;
-                bus_load
-            } else {
-                0.0
-            };
-            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| unsafe {
-                if ui.get_is_new_dbc() {
-                    NEW_DBC_CHECK = true;
-                    ui.set_is_new_dbc(false);
-                }
-                ui.set_state(bus_state.into());
-                ui.set_bitrate(bitrate as i32);
-                if busload > 0.0 {
-                    ui.set_bus_load(busload as i32);
-                }
-            });
-            unsafe {
-                if NEW_DBC_CHECK {
-                    if let Ok(dbc) = self.mspc_rx.lock().unwrap().try_recv() {
-                        self.dbc = Some(dbc);
-                        NEW_DBC_CHECK = false;
+            }
+            while let Ok((is_extended, id, data)) = self.transmit_rx.try_recv() {
+                let can_frame = if is_extended {
+                    ExtendedId::new(id).and_then(|id| CanFrame::new(id, &data))
+                } else {
+                    StandardId::new(id as u16).and_then(|id| CanFrame::new(id, &data))
+                };
+                match can_frame {
+                    Some(can_frame) => {
+                        let _ = can_socket.write_frame(&can_frame);
+                    }
+                    None => {
+                        println!("Invalid CAN frame {:08X} {:?}", id, data)
                     }
                 }
             }
-            if let Ok(frame) = rx_can_socket.read_frame() {
-                let _ = self.can_tx.send(frame);
-                total_bits += (frame.len() + 6) * 8; // Data length + overhead (approximation)
-                let frame_id = frame.raw_id() & !0x80000000;
-                if let Some(dbc) = &self.dbc {
-                    for message in dbc.messages() {
-                        if frame_id == (message.message_id().raw() & !0x80000000) {
-                            let padding_data = Self::pad_to_8_bytes(frame.data());
-                            let hex_string = Self::array_to_hex_string(frame.data());
-                            let signal_data = message.parse_from_can(&padding_data);
-                            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-                                let is_filter = ui.get_is_filter();
-                                let messages: ModelRc<CanData> = if !is_filter {
-                                    ui.get_messages()
-                                } else {
-                                    ui.get_filter_messages()
-                                };
-                                Self::update_ui_with_signals(
-                                    &messages,
-                                    frame_id,
-                                    signal_data,
-                                    hex_string,
-                                );
-                            });
-                        }
+            if let Ok(frame) = can_socket.read_frame() {
+                // The kernel receive time, not delayed by the scheduling of this thread
+                let time = kernel_time(&can_socket).unwrap_or_else(Local::now);
+                self.record(
+                    time,
+                    frame.raw_id() & CAN_ID_MASK,
+                    frame.is_extended(),
+                    frame.is_error_frame(),
+                    frame.len() as u8,
+                    frame.data(),
+                );
+                if frame.is_error_frame() {
+                    let error_frame = ErrorFrame::decode(frame.raw_id(), frame.data());
+                    if error_frame.counters.is_some() {
+                        error_counters = error_frame.counters;
                     }
+                    self.capture(time, CaptureEvent::Error(error_frame.description));
+                    continue;
                 }
+                let busy = bus_load.add_frame(
+                    frame.raw_id() & !0x80000000,
+                    frame.is_extended(),
+                    frame.is_remote_frame(),
+                    frame.data(),
+                );
+                self.observe(
+                    time,
+                    frame.raw_id() & !0x80000000,
+                    frame.is_extended(),
+                    frame.len(),
+                    busy,
+                );
+                self.handle_frame(
+                    time,
+                    frame.raw_id() & !0x80000000,
+                    frame.is_extended(),
+                    frame.data(),
+                );
             } else {
                 std::thread::sleep(Duration::from_millis(1));
             }
         }
     }
     #[cfg(target_os = "windows")]
-    fn process_ui_events(&mut self, can_if: UsbCanSocket) {
+    fn set_pcan_filters(can_if: &UsbCanSocket, filters: &[AcceptanceFilter]) {
+        use pcan_basic::df::{SetAcceptanceFilter11Bit, SetAcceptanceFilter29Bit};
+        let ((code, mask), (ext_code, ext_mask)) = AcceptanceFilter::pcan_code_masks(filters);
+        if let Err(e) = can_if.set_acceptance_filter_11bit(code, mask) {
+            println!("ERR: Failed to set the 11-bit acceptance filter - {:?}", e);
+        }
+        if let Err(e) = can_if.set_acceptance_filter_29bit(ext_code, ext_mask) {
+            println!("ERR: Failed to set the 29-bit acceptance filter - {:?}", e);
+        }
+    }
+    #[cfg(target_os = "windows")]
+    fn process_ui_events(&mut self, can_if: UsbCanSocket) -> bool {
         use pcan_basic::{
             error::PcanError,
-            socket::{MessageType, RecvCan, SendCan},
+            socket::{RecvCan, SendCan},
         };
-        let mut start_bus_load = Instant::now();
-        let mut total_bits = 0;
-        let refer_socket = UsbCanSocket::open_with_usb_bus(self.iface);
-        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-            ui.on_can_transmit(move |is_extended, can_id, can_data| {
+        let mut bus_load = BusLoad::new(self.bitrate().unwrap(), self.data_bitrate().unwrap());
+        let mut bus_state = SharedString::from("OK");
+        let mut clock = ReceiveClock::default();
+        // The PCAN acceptance filter is a single code/mask pair per frame format which can't hold
+        // a filter list or inverted filters, the frames are also filtered right after reception
+        let mut acceptance_filters = self.acceptance_filters.lock().unwrap().clone();
+        Self::set_pcan_filters(&can_if, &acceptance_filters);
+        loop {
+            if self.is_refresh_due() {
+                self.update_channel_state(bus_state.clone(), bus_load.sample(), None);
+                self.update_signals();
+            }
+            self.update_decoders();
+            while let Ok(command) = self.command_rx.try_recv() {
+                match command {
+                    ChannelCommand::ApplyFilters => {
+                        acceptance_filters = self.acceptance_filters.lock().unwrap().clone();
+                        Self::set_pcan_filters(&can_if, &acceptance_filters);
+                    }
+                    command => {
+                        if let Some(keep_running) = self.handle_command(command) {
+                            return keep_running;
+                        }
+                    }
+                }
+            }
+            while let Ok((is_extended, id, data)) = self.transmit_rx.try_recv() {
+                let message_type = if is_extended {
+                    MessageType::Extended
+                } else {
+                    MessageType::Standard
+                };
+                match CanFrame::new(id, message_type, &data) {
+                    Ok(can_frame) => {
+                        let _ = can_if.send(can_frame);
+                    }
+                    Err(e) => {
+                        println!("Invalid CAN frame {:08X} {:?}: {:?}", id, data, e)
+                    }
+                }
+            }
+            match can_if.recv() {
+                Ok((frame, timestamp)) => {
+                    bus_state = SharedString::from("OK");
+                    let frame_id = frame.can_id() & !0x80000000;
+                    if !AcceptanceFilter::accepts(
+                        &acceptance_filters,
+                        frame_id,
+                        frame.is_extended(),
+                    ) {
+                        continue;
+                    }
+                    let time = clock.time(pcan_timestamp(&timestamp));
+                    self.record(
+                        time,
+                        frame_id,
+                        frame.is_extended(),
+                        false,
+                        frame.dlc(),
+                        frame.data(),
+                    );
+                    // PCAN delivers remote frames with their DLC, they are counted as data frames
+                    let busy =
+                        bus_load.add_frame(frame_id, frame.is_extended(), false, frame.data());
+                    self.observe(
+                        time,
+                        frame_id,
+                        frame.is_extended(),
+                        frame.dlc() as usize,
+                        busy,
+                    );
+                    self.handle_frame(time, frame_id, frame.is_extended(), frame.data());
+                }
+                Err(e) => {
+                    if e != PcanError::QrcvEmpty {
+                        bus_state = SharedString::from(format!("{:?}", e));
+                    }
+                    sleep(Duration::from_millis(1));
+                }
+            }
+        }
+    }
+
+    fn process_backend(&mut self, name: &str) -> bool {
+        let bitrate = self.bitrate().unwrap();
+        let mut backend = match open_backend(name, bitrate, self.options.listen_only) {
+            Ok(backend) => backend,
+            Err(e) => {
+                println!("ERR: Failed to open {} - {}\nTry to re-connect...", name, e);
+                self.update_channel_state(SharedString::from(e), None, None);
+                sleep(Duration::from_millis(1000));
+                return true;
+            }
+        };
+        let mut bus_load = BusLoad::new(bitrate, self.data_bitrate().unwrap());
+        let mut bus_state = SharedString::from("OK");
+        let mut clock = ReceiveClock::default();
+        // The adapters receive all frames, they are filtered right after reception
+        let mut acceptance_filters = self.acceptance_filters.lock().unwrap().clone();
+        loop {
+            if self.is_refresh_due() {
+                self.update_channel_state(bus_state.clone(), bus_load.sample(), None);
+                self.update_signals();
+            }
+            self.update_decoders();
+            while let Ok(command) = self.command_rx.try_recv() {
+                match command {
+                    ChannelCommand::ApplyFilters => {
+                        acceptance_filters = self.acceptance_filters.lock().unwrap().clone();
+                    }
+                    command => {
+                        if let Some(keep_running) = self.handle_command(command) {
+                            return keep_running;
+                        }
+                    }
+                }
+            }
+            while let Ok((is_extended, id, data)) = self.transmit_rx.try_recv() {
+                if let Err(e) = backend.write_frame(is_extended, id, &data) {
+                    println!("Failed to send CAN frame {:08X} {:?}: {}", id, data, e)
+                }
+            }
+            match backend.read_event() {
+                Ok(Some(BackendEvent::Frame(frame))) => {
+                    bus_state = SharedString::from("OK");
+                    if !AcceptanceFilter::accepts(&acceptance_filters, frame.id, frame.is_extended)
+                    {
+                        continue;
+                    }
+                    let time = match frame.timestamp {
+                        Some(timestamp) => clock.time(timestamp),
+                        None => Local::now(),
+                    };
+                    let busy = if frame.is_fd {
+                        bus_load.add_fd_frame(frame.is_extended, frame.brs, frame.data.len())
+                    } else {
+                        bus_load.add_frame(
+                            frame.id,
+                            frame.is_extended,
+                            frame.is_remote,
+                            &frame.data,
+                        )
+                    };
+                    self.observe(time, frame.id, frame.is_extended, frame.data.len(), busy);
+                    if frame.is_remote {
+                        continue;
+                    }
+                    self.record(
+                        time,
+                        frame.id,
+                        frame.is_extended,
+                        false,
+                        frame.data.len() as u8,
+                        &frame.data,
+                    );
+                    self.handle_frame(time, frame.id, frame.is_extended, &frame.data);
+                }
+                Ok(Some(BackendEvent::Error(e))) => {
+                    bus_state = SharedString::from(e);
+                }
+                Ok(None) => {
+                    sleep(Duration::from_millis(1));
+                }
+                Err(e) => {
+                    println!("ERR: {} - {}", name, e);
+                    return true;
+                }
+            }
+        }
+    }
+
+    pub fn register_transmit(ui_handle: &Weak<AppWindow>, transmit_tx: Vec<Sender<TransmitFrame>>) {
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.on_can_transmit(move |channel, is_extended, can_id, can_data| {
+                let Some(transmit_tx) = transmit_tx.get(channel as usize) else {
+                    println!("Invalid channel {}", channel);
+                    return;
+                };
                 match Self::convert_hex_string_u32(&can_id) {
                     Ok(id) => match Self::convert_hex_string_arr(&can_data) {
                         Ok(data) => {
-                            if is_extended {
-                                let can_frame =
-                                    CanFrame::new(id, MessageType::Extended, &data).unwrap();
-                                let _ = refer_socket.send(can_frame);
-                            } else {
-                                let can_frame =
-                                    CanFrame::new(id, MessageType::Standard, &data).unwrap();
-                                let _ = refer_socket.send(can_frame);
-                            };
+                            let _ = transmit_tx.send((is_extended, id, data));
                         }
                         Err(e) => {
                             println!("Failed to parse can data {}, error {}", can_data, e);
@@ -230,116 +546,391 @@ impl<'a> CanHandler<'a> {
                 }
             });
         });
-        loop {
-            let bitrate = self.bitrate().unwrap();
-            let busload = if start_bus_load.elapsed() >= Duration::from_millis(1000) {
-                start_bus_load = Instant::now();
-                let bus_load = (total_bits as f64 / bitrate as f64) * 100.0;
-                total_bits = 0;
-                bus_load
-            } else {
-                0.0
-            };
-            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| unsafe {
-                if ui.get_is_new_dbc() {
-                    NEW_DBC_CHECK = true;
-                    ui.set_is_new_dbc(false);
-                }
-                ui.set_bitrate(bitrate as i32);
-                if busload > 0.0 {
-                    ui.set_bus_load(busload as i32);
-                }
+    }
+
+    fn record(
+        &self,
+        time: DateTime<Local>,
+        id: u32,
+        is_extended: bool,
+        is_error: bool,
+        len: u8,
+        data: &[u8],
+    ) {
+        if self.recording {
+            let _ = self.can_tx.send(TraceEntry {
+                time,
+                channel: self.channel,
+                id,
+                is_extended,
+                is_error,
+                len,
+                data: data.to_vec(),
+                previous: None,
             });
-            unsafe {
-                if NEW_DBC_CHECK {
-                    if let Ok(dbc) = self.mspc_rx.lock().unwrap().try_recv() {
-                        self.dbc = Some(dbc);
-                        NEW_DBC_CHECK = false;
+        }
+    }
+
+    fn capture(&self, time: DateTime<Local>, event: CaptureEvent) {
+        if self.capture_enabled.load(Ordering::Relaxed) {
+            let _ = self.capture_tx.send((self.channel, time, event));
+        }
+    }
+
+    fn observe(
+        &self,
+        time: DateTime<Local>,
+        id: u32,
+        is_extended: bool,
+        dlc: usize,
+        busy: Duration,
+    ) {
+        let _ = self.statistics_tx.send(ObservedFrame {
+            channel: self.channel,
+            time,
+            id,
+            is_extended,
+            dlc,
+            busy,
+        });
+    }
+
+    fn update_channel_state(
+        &self,
+        state: SharedString,
+        bus_load: Option<BusLoadSample>,
+        error_counters: Option<(u8, u8)>,
+    ) {
+        let channel = self.channel;
+        let capture_tx = self
+            .capture_enabled
+            .load(Ordering::Relaxed)
+            .then(|| self.capture_tx.clone());
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let channels = ui.get_channels();
+            if let Some(mut can_channel) = channels.row_data(channel) {
+                let (tec, rec) = match error_counters {
+                    Some((tec, rec)) => (tec as i32, rec as i32),
+                    None => (can_channel.tec, can_channel.rec),
+                };
+                if can_channel.state != state
+                    || bus_load.is_some()
+                    || can_channel.tec != tec
+                    || can_channel.rec != rec
+                {
+                    if can_channel.state != state && can_channel.state != "-" {
+                        Self::add_state_transition(&ui, channel, &can_channel.state, &state);
                     }
-                }
-            }
-            match can_if.recv_frame() {
-                Ok(frame) => {
-                    let _ = self.can_tx.send(frame);
-                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-                        ui.set_state("OK".into());
-                    });
-                    total_bits += (frame.dlc() as u32 + 6) * 8; // Data length + overhead (approximation)
-                    if let Some(dbc) = &self.dbc {
-                        let id = frame.can_id();
-                        let frame_id = id & !0x80000000;
-                        for message in dbc.messages() {
-                            if frame_id == (message.message_id().raw() & !0x80000000) {
-                                let padding_data = Self::pad_to_8_bytes(frame.data());
-                                let hex_string = Self::array_to_hex_string(frame.data());
-                                let signal_data = message.parse_from_can(&padding_data);
-                                let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-                                    let is_filter = ui.get_is_filter();
-                                    let messages: ModelRc<CanData> = if !is_filter {
-                                        ui.get_messages()
-                                    } else {
-                                        ui.get_filter_messages()
-                                    };
-                                    Self::update_ui_with_signals(
-                                        &messages,
-                                        frame_id,
-                                        signal_data,
-                                        hex_string,
-                                    );
-                                });
-                            }
-                        }
+                    if let Some(capture_tx) = capture_tx.filter(|_| can_channel.state != state) {
+                        let event = CaptureEvent::State(state.to_string());
+                        let _ = capture_tx.send((channel, Local::now(), event));
+                    }
+                    can_channel.state = state;
+                    can_channel.tec = tec;
+                    can_channel.rec = rec;
+                    if let Some(bus_load) = bus_load {
+                        can_channel.bus_load = bus_load.smoothed.round() as i32;
+                        can_channel.peak_load = bus_load.peak.round() as i32;
+                        can_channel.worst_case_load = bus_load.worst_case.round() as i32;
+                        can_channel.load_plot = bus_load.plot.into();
                     }
+                    channels.set_row_data(channel, can_channel);
                 }
-                Err(e) => {
-                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-                        if e != PcanError::QrcvEmpty {
-                            ui.set_state(format!("{:?}", e).into());
-                        }
-                    });
-                    sleep(Duration::from_millis(1));
+            }
+        });
+    }
+
+    fn add_state_transition(ui: &AppWindow, channel: usize, from: &str, to: &str) {
+        let mut history: Vec<StateTransition> = ui.get_state_history().iter().collect();
+        history.insert(
+            0,
+            StateTransition {
+                time: SharedString::from(Local::now().format("%H:%M:%S%.3f").to_string()),
+                channel: SharedString::from(format!("CH{}", channel)),
+                from: from.into(),
+                to: to.into(),
+                color: EVEN_COLOR,
+            },
+        );
+        history.truncate(MAX_STATE_HISTORY);
+        for (count, transition) in history.iter_mut().enumerate() {
+            transition.color = if count % 2 == 0 {
+                EVEN_COLOR
+            } else {
+                ODD_COLOR
+            };
+        }
+        ui.set_state_history(Rc::new(VecModel::from(history)).into());
+    }
+
+    fn handle_command(&mut self, command: ChannelCommand) -> Option<bool> {
+        match command {
+            ChannelCommand::Reconnect => return Some(true),
+            ChannelCommand::Disconnect => return Some(false),
+            ChannelCommand::SetBitrate(bitrate) => {
+                self.bitrate = bitrate;
+                return Some(true);
+            }
+            ChannelCommand::LoadDbc(dbc) => {
+                self.cycle_monitor = Some(CycleMonitor::new(dbc.dbc()));
+                self.dbc = Some(dbc);
+                self.unknown_ids.clear();
+                self.signal_updates.clear();
+            }
+            ChannelCommand::UnloadDbc => {
+                self.cycle_monitor = None;
+                self.dbc = None;
+                self.unknown_ids.clear();
+                self.signal_updates.clear();
+            }
+            ChannelCommand::SetRecording(recording) => self.recording = recording,
+            // The filters are loaded when the interface is opened
+            ChannelCommand::ApplyFilters => {}
+        }
+        None
+    }
+
+    fn update_decoders(&mut self) {
+        if let Ok(definitions) = self.n2k_rx.try_recv() {
+            self.n2k = Some(N2kDecoder::new(definitions));
+        }
+        self.update_unknown_ids();
+        self.check_cycle_times();
+    }
+
+    fn check_cycle_times(&mut self) {
+        let Some(cycle_monitor) = &mut self.cycle_monitor else {
+            return;
+        };
+        let timeouts = cycle_monitor.check(self.cycle_timeout.load(Ordering::Relaxed));
+        if timeouts.is_empty() {
+            return;
+        }
+        let channel = self.channel;
+        let time = Local::now().format("%H:%M:%S%.3f").to_string();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let mut messages = Vec::default();
+            if let Some(can_channel) = ui.get_channels().row_data(channel) {
+                messages.push(can_channel.messages);
+            }
+            if ui.get_is_filter() && ui.get_selected_channel() as usize == channel {
+                messages.push(ui.get_filter_messages());
+            }
+            let alarms = ui.get_cycle_alarms();
+            let mut alarm_list: Vec<CycleAlarm> = alarms.iter().collect();
+            for CycleTimeout {
+                id,
+                name,
+                cycle,
+                missing,
+            } in timeouts
+            {
+                let can_id = format!("{:08X}", id);
+                for messages in &messages {
+                    Self::set_cycle_missing(messages, &can_id);
                 }
+                alarm_list.insert(
+                    0,
+                    CycleAlarm {
+                        time: SharedString::from(time.as_str()),
+                        channel: SharedString::from(format!("CH{}", channel)),
+                        can_id: can_id.into(),
+                        name: name.into(),
+                        text: SharedString::from(format!(
+                            "not seen for {} ms (cycle time {} ms)",
+                            missing.as_millis(),
+                            cycle.as_millis()
+                        )),
+                        color: EVEN_COLOR,
+                    },
+                );
+            }
+            alarm_list.truncate(MAX_ALARMS);
+            for (count, alarm) in alarm_list.iter_mut().enumerate() {
+                alarm.color = if count % 2 == 0 {
+                    EVEN_COLOR
+                } else {
+                    ODD_COLOR
+                };
+            }
+            ui.set_cycle_alarms(Rc::new(VecModel::from(alarm_list)).into());
+        });
+    }
+
+    fn set_cycle_missing(messages: &ModelRc<CanData>, can_id: &str) {
+        for (message_count, mut message) in messages.iter().enumerate() {
+            if message.can_id == can_id {
+                message.cycle_state = CYCLE_MISSING;
+                message.timeouts += 1;
+                messages.set_row_data(message_count, message);
+                break;
+            }
+        }
+    }
+
+    fn handle_frame(
+        &mut self,
+        time: DateTime<Local>,
+        frame_id: u32,
+        is_extended: bool,
+        data: &[u8],
+    ) {
+        if self.gateway_enabled.load(Ordering::Relaxed) {
+            let _ = self
+                .gateway_tx
+                .send((self.channel, (is_extended, frame_id, data.to_vec())));
+        }
+        if !is_extended && OBD_RESPONSE_IDS.contains(&frame_id) {
+            let _ = self.obd_tx.send((frame_id, data.to_vec()));
+        }
+        if frame_id == self.xcp_dto_id.load(Ordering::Relaxed) {
+            let _ = self.xcp_tx.send((frame_id, data.to_vec()));
+        }
+        if let Some(cycle_monitor) = &mut self.cycle_monitor {
+            cycle_monitor.frame(frame_id, time);
+        }
+        self.capture(
+            time,
+            CaptureEvent::Frame {
+                id: frame_id,
+                is_extended,
+                data: data.to_vec(),
+            },
+        );
+        let message = self.dbc.as_ref().and_then(|dbc| dbc.message(frame_id));
+        if let Some(message) = message {
+            self.signal_updates.add(
+                &message.can_id,
+                Some(message.row),
+                time,
+                message.decode(data),
+                Self::array_to_hex_string(data),
+            );
+        } else {
+            self.unknown_ids.add(frame_id, is_extended, data, time);
+        }
+        if is_extended {
+            self.process_n2k_frame(time, frame_id, data);
+        }
+    }
+
+    fn update_unknown_ids(&mut self) {
+        let precision = self.time_format.lock().unwrap().precision;
+        let Some(rows) = self.unknown_ids.rows(precision) else {
+            return;
+        };
+        let channel = self.channel;
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let channels = ui.get_channels();
+            if let Some(mut can_channel) = channels.row_data(channel) {
+                can_channel.unknown_ids = Rc::new(VecModel::from(rows)).into();
+                channels.set_row_data(channel, can_channel);
+            }
+        });
+    }
+
+    fn process_n2k_frame(&mut self, time: DateTime<Local>, frame_id: u32, data: &[u8]) {
+        if let Some(n2k) = &mut self.n2k {
+            if let Some((pgn, signal_data)) = n2k.decode(frame_id, data) {
+                let hex_string = Self::array_to_hex_string(data);
+                self.n2k_updates
+                    .add(&format!("PGN {}", pgn), None, time, signal_data, hex_string);
             }
         }
     }
 
+    fn is_refresh_due(&mut self) -> bool {
+        if self.last_refresh.elapsed() < UI_REFRESH_INTERVAL {
+            return false;
+        }
+        self.last_refresh = Instant::now();
+        true
+    }
+
+    fn update_signals(&mut self) {
+        let updates = self.signal_updates.take();
+        let n2k_updates = self.n2k_updates.take();
+        if updates.is_none() && n2k_updates.is_none() {
+            return;
+        }
+        let channel = self.channel;
+        let precision = self.time_format.lock().unwrap().precision;
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            if let Some(updates) = updates {
+                let messages: ModelRc<CanData> =
+                    if ui.get_is_filter() && ui.get_selected_channel() as usize == channel {
+                        ui.get_filter_messages()
+                    } else {
+                        match ui.get_channels().row_data(channel) {
+                            Some(can_channel) => can_channel.messages,
+                            None => return,
+                        }
+                    };
+                Self::update_ui_with_signals(&messages, updates, precision);
+            }
+            if let Some(n2k_updates) = n2k_updates {
+                Self::update_ui_with_signals(&ui.get_n2k_messages(), n2k_updates, precision);
+            }
+        });
+    }
+
     fn update_ui_with_signals(
         messages: &ModelRc<CanData>,
-        frame_id: u32,
-        signal_data: HashMap<String, f32>,
-        raw_can: String,
+        updates: HashMap<String, MessageUpdate>,
+        precision: u32,
     ) {
-        for (message_count, message) in messages.iter().enumerate() {
-            if message.can_id == format!("{:08X}", frame_id) {
-                let now = Utc::now().timestamp_micros();
-                let can_signals = Self::create_can_signals(&message, &signal_data);
-                let circle_time =
-                    (now - (message.time_stamp).parse::<i64>().unwrap()) as f32 / 1000.0;
-                messages.set_row_data(
-                    message_count,
-                    CanData {
-                        can_id: message.can_id.clone(),
-                        packet_name: message.packet_name.clone(),
-                        signal_value: can_signals.into(),
-                        counter: message.counter + 1,
-                        raw_can: raw_can.into(),
-                        color: if message_count % 2 == 0 {
-                            EVEN_COLOR
-                        } else {
-                            ODD_COLOR
-                        },
-                        circle_time: format!("{:.02} ms", circle_time).into(),
-                        time_stamp: now.to_string().into(),
+        // Rows by ID for the messages without a row on the view page, e.g. in the filter view
+        let mut rows: Option<HashMap<String, usize>> = None;
+        for (can_id, update) in updates {
+            let row = update
+                .row
+                .and_then(|row| messages.row_data(row).map(|message| (row, message)))
+                .filter(|(_, message)| message.can_id == can_id.as_str())
+                .or_else(|| {
+                    let rows = rows.get_or_insert_with(|| {
+                        messages
+                            .iter()
+                            .enumerate()
+                            .map(|(row, message)| (message.can_id.to_string(), row))
+                            .collect()
+                    });
+                    let row = *rows.get(&can_id)?;
+                    messages.row_data(row).map(|message| (row, message))
+                });
+            let Some((message_count, message)) = row else {
+                continue;
+            };
+            let can_signals = Self::create_can_signals(&message, &update.signals);
+            let cycle_state = cycle_state(message.cycle_time, update.max_period);
+            messages.set_row_data(
+                message_count,
+                CanData {
+                    can_id: message.can_id.clone(),
+                    packet_name: message.packet_name.clone(),
+                    signal_value: can_signals.into(),
+                    counter: message.counter + update.frames,
+                    raw_can: update.raw_can.into(),
+                    color: if message_count % 2 == 0 {
+                        EVEN_COLOR
+                    } else {
+                        ODD_COLOR
                     },
-                );
-                break;
-            }
+                    circle_time: match update.period {
+                        Some(period) => TimeFormat::period(precision, period as f64).into(),
+                        None => message.circle_time.clone(),
+                    },
+                    time_stamp: update.time_stamp.to_string().into(),
+                    cycle_time: message.cycle_time,
+                    cycle_state,
+                    timeouts: message.timeouts,
+                },
+            );
         }
     }
 
-    fn create_can_signals(
+    fn create_can_signals<T: Display>(
         message: &CanData,
-        signal_data: &HashMap<String, f32>,
+        signal_data: &HashMap<String, T>,
     ) -> Rc<VecModel<CanSignal>> {
         let can_signals = Rc::new(VecModel::from(
             [CanSignal {
@@ -370,20 +961,6 @@ impl<'a> CanHandler<'a> {
         can_signals
     }
 
-    fn pad_to_8_bytes(data: &[u8]) -> Vec<u8> {
-        // Convert the byte slice to a Vec<u8>
-        let mut padded_data = data.to_vec();
-
-        // Calculate the number of padding bytes needed
-        let padding_needed = 8usize.saturating_sub(padded_data.len());
-
-        // Extend the vector with zeros (or another byte) to make it 8 bytes long
-        padded_data.extend(std::iter::repeat(0).take(padding_needed));
-
-        // Return the padded vector
-        padded_data
-    }
-
     fn convert_hex_string_u32(hex_str: &str) -> Result<u32, String> {
         // Attempt to parse the hex string as a u32
         u32::from_str_radix(hex_str, 16).map_err(|e| format!("Failed to convert to u32: {}", e))
@@ -418,27 +995,21 @@ impl<'a> CanHandler<'a> {
         hex_string
     }
 
+    fn data_bitrate(&self) -> Option<u32> {
+        match self.data_bitrate.as_str() {
+            "1 Mbit/s" => Some(1_000_000),
+            "2 Mbit/s" => Some(2_000_000),
+            "4 Mbit/s" => Some(4_000_000),
+            "5 Mbit/s" => Some(5_000_000),
+            "8 Mbit/s" => Some(8_000_000),
+            _ => None,
+        }
+    }
+
     fn bitrate(&self) -> Option<u32> {
-        let bitrate_map: HashMap<&str, u32> = [
-            ("1 Mbit/s", 1_000_000),
-            ("800 kbit/s", 800_000),
-            ("500 kbit/s", 500_000),
-            ("250 kbit/s", 250_000),
-            ("125 kbit/s", 125_000),
-            ("100 kbit/s", 100_000),
-            ("95.238 kbit/s", 95_238),
-            ("83.333 kbit/s", 83_333),
-            ("50 kbit/s", 50_000),
-            ("47.619 kbit/s", 47_619),
-            ("33.333 kbit/s", 33_333),
-            ("20 kbit/s", 20_000),
-            ("10 kbit/s", 10_000),
-            ("5 kbit/s", 5_000),
-        ]
-        .iter()
-        .cloned()
-        .collect();
-
-        bitrate_map.get(self.bitrate.as_str()).copied()
+        BITRATES
+            .iter()
+            .find(|(name, _)| *name == self.bitrate.as_str())
+            .map(|(_, bitrate)| *bitrate)
     }
 }
@@ -0,0 +1,221 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use super::backend::{host_address, BackendEvent, CanBackend, RawFrame};
+
+pub const CANNELLONI_PREFIX: &str = "cannelloni://";
+
+const CANNELLONI_PORT: u16 = 20000;
+const CANNELLONI_VERSION: u8 = 2;
+const CANNELLONI_OP_DATA: u8 = 0;
+const CANNELLONI_HEADER_LEN: usize = 5;
+const CAN_EFF_FLAG: u32 = 0x80000000;
+const CAN_RTR_FLAG: u32 = 0x40000000;
+const CANFD_FRAME: u8 = 0x80;
+const CANFD_BRS: u8 = 0x01;
+
+pub struct CannelloniSocket {
+    socket: UdpSocket,
+    remote: SocketAddr,
+    sequence: u8,
+    frames: VecDeque<RawFrame>,
+}
+
+impl CannelloniSocket {
+    pub fn open(address: &str) -> Result<Self, String> {
+        let (remote, local_port) = Self::parse_address(address)?;
+        let remote = remote
+            .to_socket_addrs()
+            .map_err(|e| format!("{}: {}", remote, e))?
+            .next()
+            .ok_or_else(|| format!("{}: address not found", remote))?;
+        let socket = UdpSocket::bind(("0.0.0.0", local_port.unwrap_or(remote.port())))
+            .map_err(|e| e.to_string())?;
+        socket
+            .set_read_timeout(Some(Duration::from_millis(1)))
+            .map_err(|e| e.to_string())?;
+        Ok(Self {
+            socket,
+            remote,
+            sequence: 0,
+            frames: VecDeque::default(),
+        })
+    }
+
+    pub fn parse_address(address: &str) -> Result<(String, Option<u16>), String> {
+        let (remote, local_port) = match address.split_once('/') {
+            Some((remote, local_port)) => (
+                remote,
+                Some(
+                    local_port
+                        .parse::<u16>()
+                        .map_err(|_| format!("Invalid local port {}", local_port))?,
+                ),
+            ),
+            None => (address, None),
+        };
+        Ok((host_address(remote, CANNELLONI_PORT)?, local_port))
+    }
+
+    /// Parse a data packet: version, op code, sequence number, frame count (BE u16), frames.
+    fn parse_packet(&mut self, packet: &[u8]) {
+        if packet.len() < CANNELLONI_HEADER_LEN
+            || packet[0] != CANNELLONI_VERSION
+            || packet[1] != CANNELLONI_OP_DATA
+        {
+            return;
+        }
+        let count = u16::from_be_bytes([packet[3], packet[4]]);
+        let mut position = CANNELLONI_HEADER_LEN;
+        for _ in 0..count {
+            // can_id (BE u32, with EFF/RTR flags), len, [flags if CAN FD], data
+            let Some(header) = packet.get(position..position + 5) else {
+                return;
+            };
+            let can_id = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+            let mut len = header[4] as usize;
+            position += 5;
+            let is_fd = header[4] & CANFD_FRAME != 0;
+            let mut brs = false;
+            if is_fd {
+                len = (header[4] & !CANFD_FRAME) as usize;
+                let Some(flags) = packet.get(position) else {
+                    return;
+                };
+                brs = flags & CANFD_BRS != 0;
+                position += 1;
+            }
+            let is_remote = can_id & CAN_RTR_FLAG != 0;
+            let data_len = if is_remote { 0 } else { len };
+            let Some(data) = packet.get(position..position + data_len) else {
+                return;
+            };
+            position += data_len;
+            self.frames.push_back(RawFrame {
+                id: can_id & 0x1FFFFFFF,
+                is_extended: can_id & CAN_EFF_FLAG != 0,
+                is_remote,
+                is_fd,
+                brs,
+                data: data.to_vec(),
+                timestamp: None,
+            });
+        }
+    }
+}
+
+impl CanBackend for CannelloniSocket {
+    fn read_event(&mut self) -> io::Result<Option<BackendEvent>> {
+        if let Some(frame) = self.frames.pop_front() {
+            return Ok(Some(BackendEvent::Frame(frame)));
+        }
+        let mut packet = [0u8; 1500];
+        match self.socket.recv_from(&mut packet) {
+            Ok((len, _)) => {
+                self.parse_packet(&packet[..len]);
+                Ok(self.frames.pop_front().map(BackendEvent::Frame))
+            }
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_frame(&mut self, is_extended: bool, id: u32, data: &[u8]) -> io::Result<()> {
+        let can_id = if is_extended {
+            (id & 0x1FFFFFFF) | CAN_EFF_FLAG
+        } else {
+            id & 0x7FF
+        };
+        let mut packet = vec![CANNELLONI_VERSION, CANNELLONI_OP_DATA, self.sequence, 0, 1];
+        packet.extend_from_slice(&can_id.to_be_bytes());
+        packet.push(data.len() as u8);
+        packet.extend_from_slice(data);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.socket.send_to(&packet, self.remote).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn read_frame(socket: &mut CannelloniSocket) -> RawFrame {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if let Some(BackendEvent::Frame(frame)) = socket.read_event().unwrap() {
+                return frame;
+            }
+        }
+        panic!("no frame received");
+    }
+
+    #[test]
+    fn parses_classic_and_fd_frames() {
+        let mut socket = CannelloniSocket::open("127.0.0.1:1/0").unwrap();
+        let mut packet = vec![CANNELLONI_VERSION, CANNELLONI_OP_DATA, 7, 0, 3];
+        // Standard frame 0x123 with 2 bytes
+        packet.extend_from_slice(&[0x00, 0x00, 0x01, 0x23, 2, 0xAA, 0xBB]);
+        // Extended remote frame, no data
+        packet.extend_from_slice(&[0xC0, 0x00, 0x04, 0x56, 8]);
+        // CAN FD frame with bitrate switch and 12 bytes
+        packet.extend_from_slice(&[0x80, 0x01, 0x02, 0x03, CANFD_FRAME | 12, CANFD_BRS]);
+        packet.extend_from_slice(&[0x11; 12]);
+        socket.parse_packet(&packet);
+
+        let frame = socket.frames.pop_front().unwrap();
+        assert_eq!(
+            (frame.id, frame.is_extended, frame.is_remote),
+            (0x123, false, false)
+        );
+        assert_eq!(frame.data, vec![0xAA, 0xBB]);
+        let frame = socket.frames.pop_front().unwrap();
+        assert_eq!(
+            (frame.id, frame.is_extended, frame.is_remote),
+            (0x456, true, true)
+        );
+        assert!(frame.data.is_empty());
+        let frame = socket.frames.pop_front().unwrap();
+        assert_eq!((frame.id, frame.is_fd, frame.brs), (0x10203, true, true));
+        assert_eq!(frame.data, vec![0x11; 12]);
+        assert!(socket.frames.is_empty());
+
+        // Truncated packets and other op codes are dropped
+        socket.parse_packet(&[CANNELLONI_VERSION, CANNELLONI_OP_DATA, 8, 0, 1, 0, 0]);
+        socket.parse_packet(&[CANNELLONI_VERSION, 1, 9, 0, 0]);
+        assert!(socket.frames.is_empty());
+    }
+
+    #[test]
+    fn exchanges_packets_with_peer() {
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        peer.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let address = format!("127.0.0.1:{}/0", peer.local_addr().unwrap().port());
+        let mut socket = CannelloniSocket::open(&address).unwrap();
+
+        socket.write_frame(true, 0x18FF0001, &[0x01, 0x02]).unwrap();
+        socket.write_frame(false, 0x7FF, &[]).unwrap();
+        let mut packet = [0u8; 64];
+        let (len, source) = peer.recv_from(&mut packet).unwrap();
+        assert_eq!(
+            &packet[..len],
+            &[2, 0, 0, 0, 1, 0x98, 0xFF, 0x00, 0x01, 2, 0x01, 0x02]
+        );
+        let (len, _) = peer.recv_from(&mut packet).unwrap();
+        assert_eq!(&packet[..len], &[2, 0, 1, 0, 1, 0x00, 0x00, 0x07, 0xFF, 0]);
+
+        let source = SocketAddr::from(([127, 0, 0, 1], source.port()));
+        peer.send_to(&[2, 0, 0, 0, 1, 0x00, 0x00, 0x01, 0x23, 1, 0x42], source)
+            .unwrap();
+        let frame = read_frame(&mut socket);
+        assert_eq!((frame.id, frame.data), (0x123, vec![0x42]));
+    }
+}
@@ -0,0 +1,228 @@
+use can_dbc::{AttributeValue, AttributeValuedForObjectType, MessageId, DBC};
+use chrono::{DateTime, Local};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+pub const CYCLE_OK: i32 = 0;
+pub const CYCLE_LATE: i32 = 1;
+pub const CYCLE_MISSING: i32 = 2;
+const LATE_TOLERANCE: f32 = 1.5;
+pub const DEFAULT_TIMEOUT_FACTOR: u32 = 3;
+
+const CYCLE_TIME_ATTRIBUTE: &str = "GenMsgCycleTime";
+const CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+pub fn cycle_times(dbc: &DBC) -> HashMap<u32, u64> {
+    let default = dbc
+        .attribute_defaults()
+        .iter()
+        .find(|default| default.attribute_name() == CYCLE_TIME_ATTRIBUTE)
+        .and_then(|default| attribute_ms(default.attribute_value()))
+        .filter(|ms| *ms > 0);
+    let mut cycle_times: HashMap<u32, u64> = HashMap::new();
+    if let Some(default) = default {
+        for message in dbc.messages() {
+            cycle_times.insert(message.message_id().raw() & !0x80000000, default);
+        }
+    }
+    for attribute in dbc.attribute_values() {
+        if attribute.attribute_name() != CYCLE_TIME_ATTRIBUTE {
+            continue;
+        }
+        if let AttributeValuedForObjectType::MessageDefinitionAttributeValue(
+            message_id,
+            Some(value),
+        ) = attribute.attribute_value()
+        {
+            let id = match message_id {
+                MessageId::Standard(id) => *id as u32,
+                MessageId::Extended(id) => *id,
+            };
+            match attribute_ms(value) {
+                Some(ms) if ms > 0 => {
+                    cycle_times.insert(id, ms);
+                }
+                _ => {
+                    // Event messages have a cycle time of 0
+                    cycle_times.remove(&id);
+                }
+            }
+        }
+    }
+    cycle_times
+}
+
+fn attribute_ms(value: &AttributeValue) -> Option<u64> {
+    match value {
+        AttributeValue::AttributeValueU64(ms) => Some(*ms),
+        AttributeValue::AttributeValueI64(ms) => u64::try_from(*ms).ok(),
+        AttributeValue::AttributeValueF64(ms) if *ms >= 0.0 => Some(*ms as u64),
+        _ => None,
+    }
+}
+
+pub fn cycle_state(cycle_time: f32, max_period: Option<f32>) -> i32 {
+    // The first frame has no period
+    match max_period {
+        Some(max_period) if cycle_time > 0.0 && max_period > cycle_time * LATE_TOLERANCE => {
+            CYCLE_LATE
+        }
+        _ => CYCLE_OK,
+    }
+}
+
+struct MonitoredMessage {
+    name: String,
+    cycle: Duration,
+    last_seen: DateTime<Local>,
+    timed_out: bool,
+}
+
+pub struct CycleTimeout {
+    pub id: u32,
+    pub name: String,
+    pub cycle: Duration,
+    pub missing: Duration,
+}
+
+pub struct CycleMonitor {
+    messages: HashMap<u32, MonitoredMessage>,
+    last_check: Instant,
+}
+
+impl CycleMonitor {
+    pub fn new(dbc: &DBC) -> Self {
+        let cycle_times = cycle_times(dbc);
+        let now = Local::now();
+        let messages = dbc
+            .messages()
+            .iter()
+            .filter_map(|message| {
+                let id = message.message_id().raw() & !0x80000000;
+                let ms = cycle_times.get(&id)?;
+                Some((
+                    id,
+                    MonitoredMessage {
+                        name: message.message_name().to_string(),
+                        cycle: Duration::from_millis(*ms),
+                        // Messages which are never received time out after the start
+                        last_seen: now,
+                        timed_out: false,
+                    },
+                ))
+            })
+            .collect();
+        Self {
+            messages,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub fn frame(&mut self, id: u32, time: DateTime<Local>) {
+        if let Some(message) = self.messages.get_mut(&id) {
+            message.last_seen = time;
+            message.timed_out = false;
+        }
+    }
+
+    pub fn check(&mut self, timeout_factor: u32) -> Vec<CycleTimeout> {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return Vec::default();
+        }
+        self.last_check = Instant::now();
+        self.timeouts(Local::now(), timeout_factor)
+    }
+
+    fn timeouts(&mut self, now: DateTime<Local>, timeout_factor: u32) -> Vec<CycleTimeout> {
+        let mut timeouts = Vec::default();
+        for (id, message) in self.messages.iter_mut() {
+            let missing = (now - message.last_seen).to_std().unwrap_or_default();
+            if !message.timed_out && missing > message.cycle * timeout_factor {
+                message.timed_out = true;
+                timeouts.push(CycleTimeout {
+                    id: *id,
+                    name: message.name.clone(),
+                    cycle: message.cycle,
+                    missing,
+                });
+            }
+        }
+        timeouts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+
+    fn time(ms: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap() + TimeDelta::milliseconds(ms)
+    }
+
+    fn monitored(cycle_ms: u64) -> CycleMonitor {
+        let message = MonitoredMessage {
+            name: "Engine".to_string(),
+            cycle: Duration::from_millis(cycle_ms),
+            last_seen: time(0),
+            timed_out: false,
+        };
+        CycleMonitor {
+            messages: HashMap::from([(0x100, message)]),
+            last_check: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn marks_late_messages() {
+        assert_eq!(cycle_state(100.0, Some(150.0)), CYCLE_OK);
+        assert_eq!(cycle_state(100.0, Some(150.5)), CYCLE_LATE);
+        assert_eq!(cycle_state(100.0, Some(20.0)), CYCLE_OK);
+        assert_eq!(cycle_state(100.0, None), CYCLE_OK);
+        // Messages without a cycle time are never late
+        assert_eq!(cycle_state(0.0, Some(1000.0)), CYCLE_OK);
+    }
+
+    #[test]
+    fn reports_timeouts_once() {
+        let mut monitor = monitored(100);
+        assert!(monitor
+            .timeouts(time(300), DEFAULT_TIMEOUT_FACTOR)
+            .is_empty());
+        let timeouts = monitor.timeouts(time(301), DEFAULT_TIMEOUT_FACTOR);
+        assert_eq!(timeouts.len(), 1);
+        assert_eq!(timeouts[0].id, 0x100);
+        assert_eq!(timeouts[0].name, "Engine");
+        assert_eq!(timeouts[0].cycle, Duration::from_millis(100));
+        assert_eq!(timeouts[0].missing, Duration::from_millis(301));
+        assert!(monitor
+            .timeouts(time(1000), DEFAULT_TIMEOUT_FACTOR)
+            .is_empty());
+        // Received again, the next timeout counts from this frame
+        monitor.frame(0x100, time(1000));
+        monitor.frame(0x200, time(1000));
+        assert!(monitor
+            .timeouts(time(1300), DEFAULT_TIMEOUT_FACTOR)
+            .is_empty());
+        assert_eq!(
+            monitor.timeouts(time(1301), DEFAULT_TIMEOUT_FACTOR).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn uses_timeout_factor() {
+        let mut monitor = monitored(20);
+        assert!(monitor.timeouts(time(100), 5).is_empty());
+        assert_eq!(monitor.timeouts(time(101), 5).len(), 1);
+        // Frame times ahead of the local clock are not missing
+        let mut monitor = monitored(20);
+        monitor.frame(0x100, time(500));
+        assert!(monitor.timeouts(time(400), 1).is_empty());
+        // Only checked every CHECK_INTERVAL
+        monitor.last_check = Instant::now();
+        assert!(monitor.check(1).is_empty());
+    }
+}
@@ -7,15 +7,21 @@ use std::io::Read;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
+use std::sync::Arc;
 
 use crate::slint_generatedAppWindow::AppWindow;
 use crate::slint_generatedAppWindow::CanData;
 use crate::slint_generatedAppWindow::CanSignal;
 
+use super::can_handler::ChannelCommand;
+use super::cycle_monitor::{cycle_times, CYCLE_OK};
+use super::dbc_table::DbcTable;
+use super::expression::ChannelDbcs;
 use super::{EVEN_COLOR, ODD_COLOR};
 pub struct DBCFile<'a> {
     pub ui_handle: &'a Weak<AppWindow>,
-    pub mspc_tx: &'a Sender<DBC>,
+    pub command_tx: &'a Sender<ChannelCommand>,
+    pub trace_dbcs: &'a ChannelDbcs,
 }
 
 impl<'a> DBCFile<'a> {
@@ -23,9 +29,10 @@ impl<'a> DBCFile<'a> {
         let dbc_data = Self::read_dbc_data(Self::open_dbc_file());
         match dbc_data {
             Some(dbc) => {
-                let dbc_clone = dbc.clone();
+                // The decoding table is shared by the channel, the trace and the trigger
+                let table = Arc::new(DbcTable::new(dbc));
+                let dbc = table.dbc();
                 let ui = self.ui_handle.unwrap();
-                ui.set_is_new_dbc(true);
                 ui.set_is_filter(false);
                 // Remove all filter data when open new DBC file
                 let list_filter: Vec<CanData> = [].to_vec();
@@ -41,10 +48,14 @@ impl<'a> DBCFile<'a> {
                         color: ODD_COLOR,
                         circle_time: "0.0".into(),
                         time_stamp: "0".into(),
+                        cycle_time: 0.0,
+                        cycle_state: CYCLE_OK,
+                        timeouts: 0,
                     }]
                     .to_vec(),
                 ));
 
+                let cycle_times = cycle_times(dbc);
                 let filter_list: Rc<VecModel<SharedString>> =
                     Rc::new(VecModel::from([SharedString::from("default")].to_vec()));
                 for (message_count, message) in dbc.messages().iter().enumerate() {
@@ -87,6 +98,12 @@ impl<'a> DBCFile<'a> {
                         },
                         circle_time: "0.0".into(),
                         time_stamp: "0".into(),
+                        cycle_time: cycle_times
+                            .get(&(message.message_id().raw() & !0x80000000))
+                            .copied()
+                            .unwrap_or_default() as f32,
+                        cycle_state: CYCLE_OK,
+                        timeouts: 0,
                     };
 
                     if message_count == 0 {
@@ -107,8 +124,16 @@ impl<'a> DBCFile<'a> {
                     }
                 }
 
+                // The DBC file is assigned to the channel shown on the view page
+                let channels = ui.get_channels();
+                let channel = ui.get_selected_channel() as usize;
+                if let Some(mut can_channel) = channels.row_data(channel) {
+                    can_channel.messages = message_vec.clone().into();
+                    channels.set_row_data(channel, can_channel);
+                }
                 ui.set_messages(message_vec.into());
-                let _ = self.mspc_tx.send(dbc_clone);
+                let _ = self.command_tx.send(ChannelCommand::LoadDbc(table.clone()));
+                self.trace_dbcs.lock().unwrap().insert(channel, table);
             }
             None => {
                 println!("ERR: Failed to read DBC data");
@@ -0,0 +1,313 @@
+use can_dbc::{ByteOrder, MultiplexIndicator, Signal, ValueType, DBC};
+use std::collections::HashMap;
+
+pub struct SignalLayout {
+    name: String,
+    /// Intel signals are read from the little endian frame data, Motorola signals from big endian
+    little_endian: bool,
+    shift: u32,
+    mask: u64,
+    size: u32,
+    signed: bool,
+    factor: f64,
+    offset: f64,
+    multiplexer: Option<u64>,
+}
+
+impl SignalLayout {
+    /// `start_bit` is the DBC start bit: the LSB of Intel signals, the MSB of Motorola signals.
+    pub fn new(
+        name: &str,
+        start_bit: u64,
+        size: u64,
+        little_endian: bool,
+        signed: bool,
+        factor: f64,
+        offset: f64,
+    ) -> Option<Self> {
+        if size == 0 || size > 64 {
+            return None;
+        }
+        let shift = if little_endian {
+            start_bit
+        } else {
+            // Motorola start bits count the bits of each byte from the LSB, the MSB first
+            // position of the start bit is the distance from the MSB of the first byte
+            let msb = (start_bit / 8) * 8 + (7 - start_bit % 8);
+            64u64.checked_sub(msb + size)?
+        };
+        if shift + size > 64 {
+            return None;
+        }
+        Some(Self {
+            name: name.to_string(),
+            little_endian,
+            shift: shift as u32,
+            mask: if size == 64 {
+                u64::MAX
+            } else {
+                (1 << size) - 1
+            },
+            size: size as u32,
+            signed,
+            factor,
+            offset,
+            multiplexer: None,
+        })
+    }
+
+    fn from_dbc(signal: &Signal) -> Option<Self> {
+        let mut layout = Self::new(
+            signal.name(),
+            signal.start_bit,
+            signal.signal_size,
+            matches!(signal.byte_order(), ByteOrder::LittleEndian),
+            matches!(signal.value_type(), ValueType::Signed),
+            signal.factor,
+            signal.offset,
+        )?;
+        layout.multiplexer = match signal.multiplexer_indicator() {
+            MultiplexIndicator::MultiplexedSignal(value)
+            | MultiplexIndicator::MultiplexorAndMultiplexedSignal(value) => Some(*value),
+            _ => None,
+        };
+        Some(layout)
+    }
+
+    fn raw(&self, frame: &FrameData) -> u64 {
+        let word = if self.little_endian {
+            frame.little_endian
+        } else {
+            frame.big_endian
+        };
+        (word >> self.shift) & self.mask
+    }
+
+    fn value(&self, frame: &FrameData) -> f64 {
+        let raw = self.raw(frame);
+        let value = if self.signed && self.size < 64 && (raw >> (self.size - 1)) & 1 == 1 {
+            (raw as i64 - (1i64 << self.size)) as f64
+        } else if self.signed {
+            raw as i64 as f64
+        } else {
+            raw as f64
+        };
+        value * self.factor + self.offset
+    }
+}
+
+struct FrameData {
+    little_endian: u64,
+    big_endian: u64,
+}
+
+impl FrameData {
+    fn new(data: &[u8]) -> Self {
+        let mut bytes = [0; 8];
+        let len = data.len().min(bytes.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+        Self {
+            little_endian: u64::from_le_bytes(bytes),
+            big_endian: u64::from_be_bytes(bytes),
+        }
+    }
+}
+
+pub struct MessageLayout {
+    pub name: String,
+    pub can_id: String,
+    pub row: usize,
+    signals: Vec<SignalLayout>,
+    multiplexor: Option<usize>,
+}
+
+impl MessageLayout {
+    pub fn new(name: &str, id: u32, row: usize, signals: Vec<SignalLayout>) -> Self {
+        Self {
+            name: name.to_string(),
+            can_id: format!("{:08X}", id),
+            row,
+            signals,
+            multiplexor: None,
+        }
+    }
+
+    pub fn decode(&self, data: &[u8]) -> HashMap<String, f64> {
+        let frame = FrameData::new(data);
+        let multiplexor = self.multiplexor_value(&frame);
+        self.signals
+            .iter()
+            .filter(|signal| signal.multiplexer.is_none() || signal.multiplexer == multiplexor)
+            .map(|signal| (signal.name.clone(), signal.value(&frame)))
+            .collect()
+    }
+
+    pub fn signal(&self, name: &str, data: &[u8]) -> Option<f64> {
+        let frame = FrameData::new(data);
+        let signal = self.signals.iter().find(|signal| signal.name == name)?;
+        if signal.multiplexer.is_some() && signal.multiplexer != self.multiplexor_value(&frame) {
+            return None;
+        }
+        Some(signal.value(&frame))
+    }
+
+    fn multiplexor_value(&self, frame: &FrameData) -> Option<u64> {
+        self.multiplexor
+            .map(|multiplexor| self.signals[multiplexor].raw(frame))
+    }
+}
+
+pub struct DbcTable {
+    dbc: DBC,
+    messages: HashMap<u32, MessageLayout>,
+}
+
+impl DbcTable {
+    pub fn new(dbc: DBC) -> Self {
+        let mut messages = HashMap::new();
+        for (row, message) in dbc.messages().iter().enumerate() {
+            let id = message.message_id().raw() & !0x80000000;
+            let mut signals = Vec::new();
+            let mut multiplexor = None;
+            for signal in message.signals() {
+                let Some(layout) = SignalLayout::from_dbc(signal) else {
+                    println!(
+                        "ERR: Signal {} of {} doesn't fit into 8 bytes",
+                        signal.name(),
+                        message.message_name()
+                    );
+                    continue;
+                };
+                if matches!(
+                    signal.multiplexer_indicator(),
+                    MultiplexIndicator::Multiplexor
+                        | MultiplexIndicator::MultiplexorAndMultiplexedSignal(_)
+                ) {
+                    multiplexor = Some(signals.len());
+                }
+                signals.push(layout);
+            }
+            let mut layout = MessageLayout::new(message.message_name(), id, row, signals);
+            layout.multiplexor = multiplexor;
+            // The first definition of an ID is used, like the view page does
+            messages.entry(id).or_insert(layout);
+        }
+        Self { dbc, messages }
+    }
+
+    pub fn dbc(&self) -> &DBC {
+        &self.dbc
+    }
+
+    pub fn message(&self, id: u32) -> Option<&MessageLayout> {
+        self.messages.get(&id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SG_ <name> : <start bit>|<size>@<1 Intel, 0 Motorola><+/-> (<factor>,<offset>)
+    fn signal(start_bit: u64, size: u64, little_endian: bool, signed: bool) -> SignalLayout {
+        SignalLayout::new("S", start_bit, size, little_endian, signed, 1.0, 0.0).unwrap()
+    }
+
+    fn value(layout: &SignalLayout, data: &[u8]) -> f64 {
+        layout.value(&FrameData::new(data))
+    }
+
+    #[test]
+    fn extracts_intel_signals() {
+        // SG_ EngineSpeed : 24|16@1+ (0.125,0) of the J1939 EEC1
+        let speed = SignalLayout::new("EngineSpeed", 24, 16, true, false, 0.125, 0.0).unwrap();
+        assert_eq!(value(&speed, &[0, 0, 0, 0x40, 0x1F, 0, 0, 0]), 1000.0);
+        // SG_ CoolantTemp : 0|8@1+ (1,-40)
+        let temperature = SignalLayout::new("CoolantTemp", 0, 8, true, false, 1.0, -40.0).unwrap();
+        assert_eq!(value(&temperature, &[0x64]), 60.0);
+        // 12|10@1+: the low 4 bits in byte 1, the high 6 bits in byte 2
+        assert_eq!(
+            value(&signal(12, 10, true, false), &[0, 0xA0, 0xEB]),
+            0x2BA as f64
+        );
+        assert_eq!(
+            value(&signal(0, 64, true, false), &[0xFF; 8]),
+            u64::MAX as f64
+        );
+        // Missing bytes of short frames are zero
+        assert_eq!(value(&speed, &[0, 0, 0, 0x40]), 8.0);
+    }
+
+    #[test]
+    fn extracts_motorola_signals() {
+        // SG_ VehicleSpeed : 7|16@0+ (0.01,0)
+        let speed = SignalLayout::new("VehicleSpeed", 7, 16, false, false, 0.01, 0.0).unwrap();
+        assert_eq!(value(&speed, &[0x27, 0x10]), 100.0);
+        // 3|12@0+: the MSB is bit 3 of byte 0, the low 8 bits are byte 1
+        assert_eq!(
+            value(&signal(3, 12, false, false), &[0xFE, 0x34]),
+            0xE34 as f64
+        );
+        // 13|10@0+: bits 5..0 of byte 1, then bits 7..4 of byte 2
+        assert_eq!(
+            value(
+                &signal(13, 10, false, false),
+                &[0xFF, 0b1110_1011, 0b1101_1111]
+            ),
+            0b10_1011_1101 as f64
+        );
+        assert_eq!(
+            value(&signal(7, 64, false, false), &[0xFF; 8]),
+            u64::MAX as f64
+        );
+        // The same bytes read as Intel
+        assert_eq!(
+            value(&signal(0, 16, true, false), &[0x27, 0x10]),
+            0x1027 as f64
+        );
+    }
+
+    #[test]
+    fn extends_signs() {
+        // SG_ Temperature : 0|12@1- (0.1,0)
+        let temperature = SignalLayout::new("Temperature", 0, 12, true, true, 0.1, 0.0).unwrap();
+        assert_eq!(value(&temperature, &[0x9C, 0x0F]), -10.0);
+        assert_eq!(value(&temperature, &[0x64, 0xF0]), 10.0);
+        assert_eq!(value(&signal(7, 16, false, true), &[0xFF, 0x38]), -200.0);
+        assert_eq!(value(&signal(7, 16, false, true), &[0x7F, 0xFF]), 32767.0);
+        assert_eq!(value(&signal(4, 4, true, true), &[0x80]), -8.0);
+        assert_eq!(value(&signal(0, 64, true, true), &[0xFF; 8]), -1.0);
+        assert_eq!(value(&signal(0, 1, true, true), &[0x01]), -1.0);
+    }
+
+    #[test]
+    fn rejects_signals_outside_the_frame() {
+        assert!(SignalLayout::new("S", 0, 0, true, false, 1.0, 0.0).is_none());
+        assert!(SignalLayout::new("S", 0, 65, true, false, 1.0, 0.0).is_none());
+        assert!(SignalLayout::new("S", 60, 8, true, false, 1.0, 0.0).is_none());
+        assert!(SignalLayout::new("S", 59, 8, false, false, 1.0, 0.0).is_none());
+        assert!(SignalLayout::new("S", 56, 8, true, false, 1.0, 0.0).is_some());
+        assert!(SignalLayout::new("S", 63, 8, false, false, 1.0, 0.0).is_some());
+    }
+
+    #[test]
+    fn decodes_multiplexed_signals() {
+        let mut multiplexed = signal(56, 8, true, false);
+        multiplexed.name = "Value".to_string();
+        multiplexed.multiplexer = Some(2);
+        let mut selector = signal(48, 8, true, false);
+        selector.name = "Mux".to_string();
+        let mut message = MessageLayout::new("M", 0x123, 4, vec![multiplexed, selector]);
+        message.multiplexor = Some(1);
+        assert_eq!(message.can_id, "00000123");
+        let values = message.decode(&[0, 0, 0, 0, 0, 0, 1, 9]);
+        assert!(!values.contains_key("Value"));
+        assert_eq!(values["Mux"], 1.0);
+        assert_eq!(message.signal("Value", &[0, 0, 0, 0, 0, 0, 1, 9]), None);
+        assert_eq!(
+            message.signal("Value", &[0, 0, 0, 0, 0, 0, 2, 9]),
+            Some(9.0)
+        );
+        assert_eq!(message.signal("Other", &[0; 8]), None);
+    }
+}
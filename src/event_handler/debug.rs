@@ -1,24 +1,28 @@
 use std::{
-    collections::HashMap,
-    rc::Rc,
-    sync::mpsc::{self, Receiver},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 
-use crate::slint_generatedAppWindow::{raw_can, AppWindow};
-use chrono::Local;
-#[cfg(target_os = "windows")]
-use pcan_basic::socket::CanFrame;
-use slint::{Model, SharedString, VecModel, Weak};
-#[cfg(target_os = "linux")]
-use socketcan::{CanFrame, EmbeddedFrame, Frame};
+use crate::slint_generatedAppWindow::AppWindow;
+
+use super::acceptance_filter::AcceptanceFilter;
+use super::can_handler::ChannelCommand;
+use super::expression::{signal_value, ChannelDbcs, FilterExpression, TraceFrame};
+use super::id_trace::IdTraceModel;
+use super::signal_updates::UI_REFRESH_INTERVAL;
+use super::trace::{TraceEntry, TraceModel};
+use slint::{Model, SharedString, Weak};
 
-const MAX_LEN: usize = 1000;
 pub struct DebugHandler<'a> {
     pub ui_handle: &'a Weak<AppWindow>,
-    pub bitrate: String,
-    pub filter: (u32, u32),
-    pub can_rx: Receiver<CanFrame>,
+    pub filter: Vec<AcceptanceFilter>,
+    pub expression: Option<FilterExpression>,
+    pub dbcs: ChannelDbcs,
+    pub can_rx: Receiver<TraceEntry>,
+    pub command_tx: Arc<Mutex<Vec<Sender<ChannelCommand>>>>,
 }
 
 impl<'a> DebugHandler<'a> {
@@ -26,86 +30,112 @@ impl<'a> DebugHandler<'a> {
         let (tx, rx) = mpsc::channel();
         let mut debug_enable = false;
         let tx_clone = tx.clone();
+        let (filter_tx, filter_rx) = mpsc::channel();
+        let (expression_tx, expression_rx) = mpsc::channel();
+        let command_tx = self.command_tx.clone();
         let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
             ui.on_change_state(move |state| {
+                for command_tx in command_tx.lock().unwrap().iter() {
+                    let _ = command_tx.send(ChannelCommand::SetRecording(state));
+                }
                 let _ = tx_clone.send(state);
             });
+            let ui_handle = ui.as_weak();
+            ui.on_set_display_filter(move |filter| {
+                let ui = ui_handle.unwrap();
+                match AcceptanceFilter::parse(&filter) {
+                    Ok(filter) => {
+                        ui.set_display_filter_status(SharedString::from(if filter.is_empty() {
+                            "All frames are shown".to_string()
+                        } else {
+                            format!("{} filter(s) applied", filter.len())
+                        }));
+                        let _ = filter_tx.send(filter);
+                    }
+                    Err(e) => ui.set_display_filter_status(SharedString::from(e)),
+                }
+            });
+            let ui_handle = ui.as_weak();
+            ui.on_set_trace_expression(move |expression| {
+                let ui = ui_handle.unwrap();
+                if expression.trim().is_empty() {
+                    ui.set_trace_expression_error(SharedString::default());
+                    let _ = expression_tx.send(None);
+                    return;
+                }
+                // An invalid expression keeps the previous one active
+                match FilterExpression::parse(&expression) {
+                    Ok(expression) => {
+                        ui.set_trace_expression_error(SharedString::default());
+                        let _ = expression_tx.send(Some(expression));
+                    }
+                    Err(e) => ui.set_trace_expression_error(SharedString::from(e)),
+                }
+            });
         });
         loop {
             if let Ok(en) = rx.try_recv() {
                 debug_enable = en;
+                if !debug_enable {
+                    // Frames received before the channels stopped sending are dropped
+                    while self.can_rx.try_recv().is_ok() {}
+                }
+            }
+            if let Ok(filter) = filter_rx.try_recv() {
+                self.filter = filter;
+            }
+            if let Ok(expression) = expression_rx.try_recv() {
+                self.expression = expression;
             }
             if debug_enable {
-                if let Ok(frame) = self.can_rx.try_recv() {
-                    #[cfg(target_os = "windows")]
-                    let frame_id = frame.can_id() & !0x80000000;
-                    #[cfg(target_os = "linux")]
-                    let frame_id = frame.raw_id() & !0x80000000;
-                    if frame_id >= self.filter.0 && frame_id <= self.filter.1 {
-                        let bitrate = self.bitrate().unwrap();
-                        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
-                            ui.set_bitrate(bitrate as i32);
-                            let raw_data = ui.get_raw_data();
-                            let mut vec_data = Vec::default();
-                            for data in raw_data.iter() {
-                                vec_data.push(data);
-                            }
-                            if vec_data.len() > MAX_LEN {
-                                vec_data.remove(MAX_LEN);
-                            }
-                            vec_data.insert(
-                                0,
-                                raw_can {
-                                    time: SharedString::from(
-                                        Local::now().to_string().replace('"', "").to_string(),
-                                    ),
-                                    data: SharedString::from(format!("{:?}", frame.data())),
-                                    id: if frame.is_extended() {
-                                        SharedString::from(format!("0x{:08X}", frame_id))
-                                    } else {
-                                        SharedString::from(format!("0x{:03X}", frame_id))
-                                    },
-                                    #[cfg(target_os = "linux")]
-                                    len: frame.len() as i32,
-                                    #[cfg(target_os = "windows")]
-                                    len: frame.dlc() as i32,
-                                },
-                            );
-                            let message_vec: Rc<VecModel<raw_can>> =
-                                Rc::new(VecModel::from(vec_data));
-                            ui.set_raw_data(message_vec.into());
-                        });
-                    }
-                } else {
-                    std::thread::sleep(Duration::from_millis(1));
+                // Merge the frames of all channels into one time ordered trace
+                let mut entries: Vec<TraceEntry> = self
+                    .can_rx
+                    .try_iter()
+                    .filter(|entry| self.shows(entry))
+                    .collect();
+                entries.sort_by_key(|entry| entry.time);
+                if !entries.is_empty() {
+                    // The frames are appended to the trace model, the list only shows the visible rows
+                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                        let id_trace = ui.get_id_trace();
+                        if let Some(id_trace) = id_trace.as_any().downcast_ref::<IdTraceModel>() {
+                            id_trace.update(&entries);
+                        }
+                        let raw_data = ui.get_raw_data();
+                        if let Some(trace) = raw_data.as_any().downcast_ref::<TraceModel>() {
+                            trace.push(entries);
+                        }
+                    });
                 }
+                // The frames received in the meantime are added with the next refresh
+                std::thread::sleep(UI_REFRESH_INTERVAL);
             } else {
                 std::thread::sleep(Duration::from_millis(50));
             }
         }
     }
 
-    fn bitrate(&self) -> Option<u32> {
-        let bitrate_map: HashMap<&str, u32> = [
-            ("1 Mbit/s", 1_000_000),
-            ("800 kbit/s", 800_000),
-            ("500 kbit/s", 500_000),
-            ("250 kbit/s", 250_000),
-            ("125 kbit/s", 125_000),
-            ("100 kbit/s", 100_000),
-            ("95.238 kbit/s", 95_238),
-            ("83.333 kbit/s", 83_333),
-            ("50 kbit/s", 50_000),
-            ("47.619 kbit/s", 47_619),
-            ("33.333 kbit/s", 33_333),
-            ("20 kbit/s", 20_000),
-            ("10 kbit/s", 10_000),
-            ("5 kbit/s", 5_000),
-        ]
-        .iter()
-        .cloned()
-        .collect();
-
-        bitrate_map.get(self.bitrate.as_str()).copied()
+    fn shows(&self, entry: &TraceEntry) -> bool {
+        // Error frames are always shown
+        if entry.is_error {
+            return true;
+        }
+        if !AcceptanceFilter::accepts(&self.filter, entry.id, entry.is_extended) {
+            return false;
+        }
+        let Some(expression) = &self.expression else {
+            return true;
+        };
+        let trace_frame = TraceFrame {
+            channel: entry.channel,
+            id: entry.id,
+            is_extended: entry.is_extended,
+            data: &entry.data,
+        };
+        let dbcs = self.dbcs.lock().unwrap();
+        let signal =
+            |message: &str, signal: &str| signal_value(&dbcs, &trace_frame, message, signal);
+        expression.matches(&trace_frame, &signal)
     }
 }
@@ -0,0 +1,249 @@
+//! Decoding of SocketCAN error frames, see `linux/can/error.h`.
+
+/// Error class bits of the CAN ID
+const CAN_ERR_TX_TIMEOUT: u32 = 0x001;
+const CAN_ERR_LOSTARB: u32 = 0x002;
+const CAN_ERR_CRTL: u32 = 0x004;
+const CAN_ERR_PROT: u32 = 0x008;
+const CAN_ERR_TRX: u32 = 0x010;
+const CAN_ERR_ACK: u32 = 0x020;
+const CAN_ERR_BUSOFF: u32 = 0x040;
+const CAN_ERR_BUSERROR: u32 = 0x080;
+const CAN_ERR_RESTARTED: u32 = 0x100;
+const CAN_ERR_CNT: u32 = 0x200;
+pub const CAN_ERR_MASK: u32 = 0x1FFFFFFF;
+
+/// Controller problems in data[1]
+const CONTROLLER_PROBLEMS: [(u8, &str); 7] = [
+    (0x01, "RX buffer overflow"),
+    (0x02, "TX buffer overflow"),
+    (0x04, "RX warning"),
+    (0x08, "TX warning"),
+    (0x10, "RX passive"),
+    (0x20, "TX passive"),
+    (0x40, "recovered to error active"),
+];
+
+/// Protocol violation types in data[2]
+const PROTOCOL_VIOLATIONS: [(u8, &str); 8] = [
+    (0x01, "bit error"),
+    (0x02, "form error"),
+    (0x04, "stuff error"),
+    (0x08, "bit0 error"),
+    (0x10, "bit1 error"),
+    (0x20, "bus overload"),
+    (0x40, "active error announcement"),
+    (0x80, "error on transmission"),
+];
+
+pub struct ErrorFrame {
+    pub description: String,
+    /// (TEC, REC) if the driver reports the error counters
+    pub counters: Option<(u8, u8)>,
+}
+
+impl ErrorFrame {
+    pub fn decode(can_id: u32, data: &[u8]) -> Self {
+        let class = can_id & CAN_ERR_MASK;
+        let byte = |index: usize| data.get(index).copied().unwrap_or_default();
+        let mut errors: Vec<String> = Vec::new();
+        if class & CAN_ERR_TX_TIMEOUT != 0 {
+            errors.push("TX timeout".into());
+        }
+        if class & CAN_ERR_LOSTARB != 0 {
+            match byte(0) {
+                0 => errors.push("lost arbitration".into()),
+                bit => errors.push(format!("lost arbitration at bit {}", bit)),
+            }
+        }
+        if class & CAN_ERR_CRTL != 0 {
+            let problems = Self::flags(byte(1), &CONTROLLER_PROBLEMS);
+            if problems.is_empty() {
+                errors.push("controller problem".into());
+            } else {
+                errors.push(format!("controller: {}", problems));
+            }
+        }
+        if class & CAN_ERR_PROT != 0 {
+            let mut violation = Self::flags(byte(2), &PROTOCOL_VIOLATIONS);
+            if violation.is_empty() {
+                violation = "protocol violation".into();
+            }
+            match Self::location(byte(3)) {
+                // The controllers report CRC errors as an unspecified error in the CRC sequence
+                Some("CRC sequence") if byte(2) == 0 => errors.push("CRC error".into()),
+                Some(location) => errors.push(format!("{} in {}", violation, location)),
+                None => errors.push(violation),
+            }
+        }
+        if class & CAN_ERR_TRX != 0 {
+            errors.push(format!("transceiver error {:02X}", byte(4)));
+        }
+        if class & CAN_ERR_ACK != 0 {
+            errors.push("no ACK".into());
+        }
+        if class & CAN_ERR_BUSOFF != 0 {
+            errors.push("bus off".into());
+        }
+        if class & CAN_ERR_BUSERROR != 0 {
+            errors.push("bus error".into());
+        }
+        if class & CAN_ERR_RESTARTED != 0 {
+            errors.push("controller restarted".into());
+        }
+        let counters = (class & CAN_ERR_CNT != 0).then(|| (byte(6), byte(7)));
+        if errors.is_empty() {
+            errors.push(format!("error class {:08X}", class));
+        }
+        Self {
+            description: errors.join(", "),
+            counters,
+        }
+    }
+
+    fn flags(value: u8, names: &[(u8, &str)]) -> String {
+        names
+            .iter()
+            .filter(|(flag, _)| value & flag != 0)
+            .map(|(_, name)| *name)
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    /// Location of a protocol violation in data[3].
+    fn location(value: u8) -> Option<&'static str> {
+        match value {
+            0x03 => Some("SOF"),
+            0x02 | 0x06 | 0x07 | 0x0F | 0x0E => Some("ID"),
+            0x04 => Some("SRR"),
+            0x05 => Some("IDE"),
+            0x0C => Some("RTR"),
+            0x0D => Some("reserved bit 1"),
+            0x09 => Some("reserved bit 0"),
+            0x0B => Some("DLC"),
+            0x0A => Some("data"),
+            0x08 => Some("CRC sequence"),
+            0x18 => Some("CRC delimiter"),
+            0x19 => Some("ACK slot"),
+            0x1B => Some("ACK delimiter"),
+            0x1A => Some("EOF"),
+            0x12 => Some("intermission"),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // linux/can/error.h
+    const CAN_ERR_FLAG: u32 = 0x20000000;
+    const CAN_ERR_CRTL_TX_WARNING: u8 = 0x08;
+    const CAN_ERR_CRTL_RX_PASSIVE: u8 = 0x10;
+    const CAN_ERR_CRTL_ACTIVE: u8 = 0x40;
+    const CAN_ERR_PROT_BIT: u8 = 0x01;
+    const CAN_ERR_PROT_FORM: u8 = 0x02;
+    const CAN_ERR_PROT_STUFF: u8 = 0x04;
+    const CAN_ERR_PROT_TX: u8 = 0x80;
+    const CAN_ERR_PROT_LOC_ID04_00: u8 = 0x0E;
+    const CAN_ERR_PROT_LOC_CRC_SEQ: u8 = 0x08;
+    const CAN_ERR_PROT_LOC_DATA: u8 = 0x0A;
+    const CAN_ERR_PROT_LOC_ACK: u8 = 0x19;
+
+    fn decode(class: u32, data: [u8; 8]) -> ErrorFrame {
+        ErrorFrame::decode(CAN_ERR_FLAG | class, &data)
+    }
+
+    #[test]
+    fn decodes_protocol_violations() {
+        let frame = |violation, location| {
+            decode(
+                CAN_ERR_PROT | CAN_ERR_BUSERROR,
+                [0, 0, violation, location, 0, 0, 0, 0],
+            )
+            .description
+        };
+        assert_eq!(
+            frame(CAN_ERR_PROT_STUFF | CAN_ERR_PROT_TX, CAN_ERR_PROT_LOC_DATA),
+            "stuff error, error on transmission in data, bus error"
+        );
+        assert_eq!(
+            frame(CAN_ERR_PROT_BIT, CAN_ERR_PROT_LOC_ID04_00),
+            "bit error in ID, bus error"
+        );
+        assert_eq!(frame(0, CAN_ERR_PROT_LOC_CRC_SEQ), "CRC error, bus error");
+        assert_eq!(
+            frame(CAN_ERR_PROT_FORM, CAN_ERR_PROT_LOC_CRC_SEQ),
+            "form error in CRC sequence, bus error"
+        );
+        assert_eq!(
+            frame(0, CAN_ERR_PROT_LOC_ACK),
+            "protocol violation in ACK slot, bus error"
+        );
+        assert_eq!(frame(CAN_ERR_PROT_FORM, 0), "form error, bus error");
+    }
+
+    #[test]
+    fn decodes_controller_problems() {
+        let frame = decode(
+            CAN_ERR_CRTL,
+            [
+                0,
+                CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_WARNING,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ],
+        );
+        assert_eq!(frame.description, "controller: TX warning, RX passive");
+        assert_eq!(
+            decode(CAN_ERR_CRTL, [0; 8]).description,
+            "controller problem"
+        );
+    }
+
+    #[test]
+    fn decodes_bus_off_and_restart() {
+        assert_eq!(decode(CAN_ERR_BUSOFF, [0; 8]).description, "bus off");
+        assert_eq!(
+            decode(
+                CAN_ERR_CRTL | CAN_ERR_RESTARTED,
+                [0, CAN_ERR_CRTL_ACTIVE, 0, 0, 0, 0, 0, 0]
+            )
+            .description,
+            "controller: recovered to error active, controller restarted"
+        );
+        assert_eq!(
+            decode(CAN_ERR_TX_TIMEOUT | CAN_ERR_ACK, [0; 8]).description,
+            "TX timeout, no ACK"
+        );
+        assert_eq!(
+            decode(CAN_ERR_LOSTARB, [12, 0, 0, 0, 0, 0, 0, 0]).description,
+            "lost arbitration at bit 12"
+        );
+        assert_eq!(
+            decode(CAN_ERR_TRX, [0, 0, 0, 0, 0x04, 0, 0, 0]).description,
+            "transceiver error 04"
+        );
+        assert_eq!(decode(0x400, [0; 8]).description, "error class 00000400");
+    }
+
+    #[test]
+    fn reads_error_counters() {
+        let frame = decode(
+            CAN_ERR_CRTL | CAN_ERR_CNT,
+            [0, CAN_ERR_CRTL_RX_PASSIVE, 0, 0, 0, 0, 96, 128],
+        );
+        assert_eq!(frame.counters, Some((96, 128)));
+        assert_eq!(frame.description, "controller: RX passive");
+        let frame = decode(CAN_ERR_CRTL, [0, 0, 0, 0, 0, 0, 96, 128]);
+        assert_eq!(frame.counters, None);
+        // Drivers which send a short frame
+        let frame = ErrorFrame::decode(CAN_ERR_FLAG | CAN_ERR_CNT, &[0, 0]);
+        assert_eq!(frame.counters, Some((0, 0)));
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use super::dbc_table::DbcTable;
+
+pub type ChannelDbcs = Arc<Mutex<HashMap<usize, Arc<DbcTable>>>>;
+
+pub struct TraceFrame<'a> {
+    pub channel: usize,
+    pub id: u32,
+    pub is_extended: bool,
+    pub data: &'a [u8],
+}
+
+pub struct FilterExpression {
+    expr: Expr,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Field {
+    Id,
+    Dlc,
+    Extended,
+    Channel,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BitOr,
+    BitAnd,
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(f64),
+    Field(Field),
+    Data(Box<Expr>),
+    Signal(String, String),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    InRange(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 18] = [
+    "&&", "||", "==", "!=", "<=", ">=", "..", "<", ">", "&", "|", "!", "-", "(", ")", "[", "]", ".",
+];
+
+impl FilterExpression {
+    pub fn parse(source: &str) -> Result<FilterExpression, String> {
+        let tokens = Self::tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            len: source.chars().count(),
+        };
+        let expr = parser.or()?;
+        match parser.tokens.get(parser.position) {
+            Some((token, column)) => Err(format!("Unexpected {} at {}", token, column)),
+            None => Ok(FilterExpression { expr }),
+        }
+    }
+
+    pub fn matches(&self, frame: &TraceFrame, signal: &dyn Fn(&str, &str) -> Option<f64>) -> bool {
+        is_true(self.expr.eval(frame, signal))
+    }
+
+    fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, String> {
+        let chars: Vec<char> = source.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let column = i + 1;
+            if c.is_whitespace() {
+                i += 1;
+            } else if c.is_ascii_digit() {
+                let start = i;
+                let value = if c == '0' && matches!(chars.get(i + 1), Some('x' | 'X')) {
+                    i += 2;
+                    while i < chars.len() && chars[i].is_ascii_hexdigit() {
+                        i += 1;
+                    }
+                    let digits: String = chars[start + 2..i].iter().collect();
+                    u64::from_str_radix(&digits, 16)
+                        .map(|value| value as f64)
+                        .ok()
+                } else {
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                    // A dot is only a decimal point if a digit follows, `1..5` is a range
+                    if chars.get(i) == Some(&'.')
+                        && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+                    {
+                        i += 1;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                    chars[start..i].iter().collect::<String>().parse().ok()
+                };
+                match value {
+                    Some(value) => tokens.push((Token::Number(value), column)),
+                    None => return Err(format!("Invalid number at {}", column)),
+                }
+            } else if c.is_alphabetic() || c == '_' {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+            } else {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                match SYMBOLS.iter().find(|symbol| rest.starts_with(*symbol)) {
+                    Some(symbol) => {
+                        tokens.push((Token::Symbol(symbol), column));
+                        i += symbol.len();
+                    }
+                    None => return Err(format!("Unexpected '{}' at {}", c, column)),
+                }
+            }
+        }
+        Ok(tokens)
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "'{}'", value),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.tokens.get(self.position), Some((Token::Symbol(s), _)) if *s == symbol) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", symbol)))
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        match self.tokens.get(self.position) {
+            Some((token, column)) => format!("{} at {}, found {}", message, column, token),
+            None => format!("{} at {}, found end of expression", message, self.len + 1),
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operators: &[(&str, BinaryOp)],
+        operand: fn(&mut Self) -> Result<Expr, String>,
+    ) -> Result<Expr, String> {
+        let mut left = operand(self)?;
+        'operators: loop {
+            for (symbol, op) in operators {
+                if self.eat(symbol) {
+                    let right = operand(self)?;
+                    left = Expr::Binary(*op, Box::new(left), Box::new(right));
+                    continue 'operators;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&&", BinaryOp::And)], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.bit_or()?;
+        if matches!(self.tokens.get(self.position), Some((Token::Ident(name), _)) if name == "in") {
+            self.position += 1;
+            let first = self.bit_or()?;
+            self.expect("..")?;
+            let last = self.bit_or()?;
+            return Ok(Expr::InRange(
+                Box::new(left),
+                Box::new(first),
+                Box::new(last),
+            ));
+        }
+        for (symbol, op) in [
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+            ("<=", BinaryOp::Le),
+            (">=", BinaryOp::Ge),
+            ("<", BinaryOp::Lt),
+            (">", BinaryOp::Gt),
+        ] {
+            if self.eat(symbol) {
+                let right = self.bit_or()?;
+                return Ok(Expr::Binary(op, Box::new(left), Box::new(right)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn bit_or(&mut self) -> Result<Expr, String> {
+        self.binary(&[("|", BinaryOp::BitOr)], Self::bit_and)
+    }
+
+    fn bit_and(&mut self) -> Result<Expr, String> {
+        self.binary(&[("&", BinaryOp::BitAnd)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            Ok(Expr::Not(Box::new(self.unary()?)))
+        } else if self.eat("-") {
+            Ok(Expr::Neg(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let Some((token, column)) = self.tokens.get(self.position).cloned() else {
+            return Err(self.error("Expected a value"));
+        };
+        self.position += 1;
+        match token {
+            Token::Number(value) => Ok(Expr::Number(value)),
+            Token::Symbol("(") => {
+                let expr = self.or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            Token::Ident(name) => {
+                if self.eat(".") {
+                    return match self.tokens.get(self.position).cloned() {
+                        Some((Token::Ident(signal), _)) => {
+                            self.position += 1;
+                            Ok(Expr::Signal(name, signal))
+                        }
+                        _ => Err(self.error("Expected a signal name")),
+                    };
+                }
+                match name.as_str() {
+                    "id" => Ok(Expr::Field(Field::Id)),
+                    "dlc" | "len" => Ok(Expr::Field(Field::Dlc)),
+                    "ext" => Ok(Expr::Field(Field::Extended)),
+                    "channel" | "ch" => Ok(Expr::Field(Field::Channel)),
+                    "data" => {
+                        self.expect("[")?;
+                        let index = self.or()?;
+                        self.expect("]")?;
+                        Ok(Expr::Data(Box::new(index)))
+                    }
+                    _ => Err(format!("Unknown field '{}' at {}", name, column)),
+                }
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.error("Expected a value"))
+            }
+        }
+    }
+}
+
+pub fn signal_value(
+    dbcs: &HashMap<usize, Arc<DbcTable>>,
+    frame: &TraceFrame,
+    message: &str,
+    signal: &str,
+) -> Option<f64> {
+    let layout = dbcs.get(&frame.channel)?.message(frame.id)?;
+    if layout.name != message {
+        return None;
+    }
+    layout.signal(signal, frame.data)
+}
+
+fn is_true(value: Option<f64>) -> bool {
+    value.is_some_and(|value| value != 0.0)
+}
+
+fn from_bool(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Expr {
+    fn eval(&self, frame: &TraceFrame, signal: &dyn Fn(&str, &str) -> Option<f64>) -> Option<f64> {
+        match self {
+            Expr::Number(value) => Some(*value),
+            Expr::Field(Field::Id) => Some(frame.id as f64),
+            Expr::Field(Field::Dlc) => Some(frame.data.len() as f64),
+            Expr::Field(Field::Extended) => Some(from_bool(frame.is_extended)),
+            Expr::Field(Field::Channel) => Some(frame.channel as f64),
+            Expr::Data(index) => {
+                let index = index.eval(frame, signal)?;
+                if index < 0.0 {
+                    return None;
+                }
+                frame.data.get(index as usize).map(|byte| *byte as f64)
+            }
+            Expr::Signal(message, name) => signal(message, name),
+            Expr::Not(expr) => expr
+                .eval(frame, signal)
+                .map(|value| from_bool(value == 0.0)),
+            Expr::Neg(expr) => expr.eval(frame, signal).map(|value| -value),
+            Expr::InRange(value, first, last) => {
+                let value = value.eval(frame, signal)?;
+                let first = first.eval(frame, signal)?;
+                let last = last.eval(frame, signal)?;
+                Some(from_bool(first <= value && value <= last))
+            }
+            Expr::Binary(BinaryOp::Or, left, right) => Some(from_bool(
+                is_true(left.eval(frame, signal)) || is_true(right.eval(frame, signal)),
+            )),
+            Expr::Binary(BinaryOp::And, left, right) => Some(from_bool(
+                is_true(left.eval(frame, signal)) && is_true(right.eval(frame, signal)),
+            )),
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(frame, signal)?;
+                let right = right.eval(frame, signal)?;
+                Some(match op {
+                    BinaryOp::Eq => from_bool(left == right),
+                    BinaryOp::Ne => from_bool(left != right),
+                    BinaryOp::Lt => from_bool(left < right),
+                    BinaryOp::Le => from_bool(left <= right),
+                    BinaryOp::Gt => from_bool(left > right),
+                    BinaryOp::Ge => from_bool(left >= right),
+                    BinaryOp::BitOr => (left as i64 | right as i64) as f64,
+                    BinaryOp::BitAnd => (left as i64 & right as i64) as f64,
+                    BinaryOp::Or | BinaryOp::And => unreachable!(),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(expression: &str, id: u32, data: &[u8]) -> bool {
+        let expression = FilterExpression::parse(expression).unwrap();
+        let frame = TraceFrame {
+            channel: 1,
+            id,
+            is_extended: false,
+            data,
+        };
+        // Engine.RPM is decoded from the first two bytes of 0x100
+        let signal = |message: &str, signal: &str| match (message, signal) {
+            ("Engine", "RPM") if id == 0x100 && data.len() >= 2 => {
+                Some(u16::from_le_bytes([data[0], data[1]]) as f64)
+            }
+            _ => None,
+        };
+        expression.matches(&frame, &signal)
+    }
+
+    #[test]
+    fn applies_precedence() {
+        // && binds tighter than ||
+        assert!(matches("id == 1 || id == 2 && ch == 0", 1, &[]));
+        assert!(!matches("(id == 1 || id == 2) && ch == 0", 1, &[]));
+        // & binds tighter than |, both tighter than comparisons
+        assert!(matches("id | 1 & 2 == 4", 4, &[]));
+        assert!(matches("id & 0xF0 | 1 == 0x21", 0x23, &[]));
+        assert!(matches("!(id == 5) || ch == 1", 5, &[]));
+        assert!(matches("-1 < 0 && !0", 0, &[]));
+    }
+
+    #[test]
+    fn includes_range_ends() {
+        assert!(matches("id in 0x100..0x1FF", 0x100, &[]));
+        assert!(matches("id in 0x100..0x1FF", 0x1FF, &[]));
+        assert!(!matches("id in 0x100..0x1FF", 0x200, &[]));
+        assert!(!matches("id in 0x100..0x1FF", 0xFF, &[]));
+        assert!(matches("dlc in 1..1", 0, &[0]));
+    }
+
+    #[test]
+    fn parses_numbers() {
+        assert!(matches("id == 0x1aB", 0x1AB, &[]));
+        assert!(matches("id == 0X10", 16, &[]));
+        assert!(matches("id > 1.5 && id < 2.5", 2, &[]));
+    }
+
+    #[test]
+    fn missing_data_is_false() {
+        let data = [0x80, 0, 0, 0, 0, 0, 0, 0];
+        assert!(matches("data[0] & 0x80 && dlc == 8", 0x100, &data));
+        assert!(!matches("data[8] == 0", 0x100, &data));
+        assert!(!matches("data[0]", 0x100, &[]));
+        assert!(!matches("data[-1] == 0", 0x100, &data));
+        assert!(matches("data[8] == 0 || id == 0x100", 0x100, &data));
+    }
+
+    #[test]
+    fn looks_up_signals() {
+        assert!(matches("Engine.RPM > 3000", 0x100, &[0xB9, 0x0B]));
+        assert!(!matches("Engine.RPM > 3000", 0x100, &[0xD0, 0x07]));
+        // Other messages and unknown signals never match
+        assert!(!matches("Engine.RPM > 3000", 0x101, &[0xB9, 0x0B]));
+        assert!(!matches("Engine.Speed >= 0", 0x100, &[0xB9, 0x0B]));
+        assert!(!matches("!(Engine.Speed >= 0)", 0x100, &[0xB9, 0x0B]));
+    }
+
+    #[test]
+    fn reports_errors() {
+        for (expression, error) in [
+            ("", "Expected a value at 1, found end of expression"),
+            ("id ==", "Expected a value at 6, found end of expression"),
+            ("id == 0x", "Invalid number at 7"),
+            ("foo > 1", "Unknown field 'foo' at 1"),
+            ("data[0", "Expected ']' at 7, found end of expression"),
+            ("id $ 1", "Unexpected '$' at 4"),
+            ("id 1", "Unexpected '1' at 4"),
+            (
+                "Engine.",
+                "Expected a signal name at 8, found end of expression",
+            ),
+            ("id in 1", "Expected '..' at 8, found end of expression"),
+            ("(id == 1", "Expected ')' at 9, found end of expression"),
+            (")", "Expected a value at 1, found ')'"),
+        ] {
+            assert_eq!(
+                FilterExpression::parse(expression).err().as_deref(),
+                Some(error),
+                "{}",
+                expression
+            );
+        }
+    }
+}
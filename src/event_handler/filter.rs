@@ -5,6 +5,7 @@ use std::rc::Rc;
 use crate::slint_generatedAppWindow::AppWindow;
 use crate::slint_generatedAppWindow::CanData;
 
+use super::cycle_monitor::CYCLE_OK;
 use super::{EVEN_COLOR, ODD_COLOR};
 pub struct PacketFilter<'a> {
     pub ui_handle: &'a Weak<AppWindow>,
@@ -31,6 +32,9 @@ impl<'a> PacketFilter<'a> {
                 signal_value: self.filter.signal_value,
                 circle_time: "0.0".into(),
                 time_stamp: "0".into(),
+                cycle_time: self.filter.cycle_time,
+                cycle_state: CYCLE_OK,
+                timeouts: self.filter.timeouts,
             });
         } else {
             // Remove filter ID
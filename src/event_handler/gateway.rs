@@ -0,0 +1,538 @@
+use rfd::FileDialog;
+use slint::{Model, ModelRc, SharedString, VecModel, Weak};
+use std::{
+    collections::HashMap,
+    fs,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::slint_generatedAppWindow::{AppWindow, GatewayRoute};
+
+use super::can_handler::TransmitFrame;
+use super::{EVEN_COLOR, ODD_COLOR};
+
+const STANDARD_ID_MAX: u32 = 0x7FF;
+const EXTENDED_ID_MAX: u32 = 0x1FFFFFFF;
+
+// The last field is the frame format, standard and extended IDs with the same bits differ
+pub enum IdMatch {
+    All,
+    // Matches if `frame_id & mask == id & mask`
+    Mask(u32, u32, bool),
+    Range(u32, u32, bool),
+}
+
+impl IdMatch {
+    fn matches(&self, is_extended: bool, id: u32) -> bool {
+        match self {
+            IdMatch::All => true,
+            IdMatch::Mask(match_id, mask, extended) => {
+                is_extended == *extended && id & mask == match_id & mask
+            }
+            IdMatch::Range(min, max, extended) => {
+                is_extended == *extended && (*min..=*max).contains(&id)
+            }
+        }
+    }
+}
+
+pub enum ByteOperation {
+    Set(usize, u8),
+    And(usize, u8),
+    Or(usize, u8),
+    Xor(usize, u8),
+}
+
+impl ByteOperation {
+    fn apply(&self, data: &mut [u8]) {
+        let (index, value) = match self {
+            ByteOperation::Set(index, value)
+            | ByteOperation::And(index, value)
+            | ByteOperation::Or(index, value)
+            | ByteOperation::Xor(index, value) => (*index, *value),
+        };
+        // Bytes behind the DLC of the frame are left untouched
+        let Some(byte) = data.get_mut(index) else {
+            return;
+        };
+        match self {
+            ByteOperation::Set(..) => *byte = value,
+            ByteOperation::And(..) => *byte &= value,
+            ByteOperation::Or(..) => *byte |= value,
+            ByteOperation::Xor(..) => *byte ^= value,
+        }
+    }
+}
+
+pub struct GatewayRule {
+    pub source: usize,
+    pub destination: usize,
+    pub id_match: IdMatch,
+    pub rewrite_id: Option<u32>,
+    pub operations: Vec<ByteOperation>,
+    pub rate_limit: Option<Duration>,
+}
+
+impl GatewayRule {
+    pub fn parse(content: &str) -> Result<Vec<GatewayRule>, String> {
+        let mut rules = Vec::new();
+        for (line_count, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let err = |reason: &str| {
+                format!(
+                    "Invalid gateway rule at line {}: {} ({})",
+                    line_count + 1,
+                    line,
+                    reason
+                )
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || fields[0] != "ROUTE" {
+                return Err(err("expected ROUTE <source> <destination> <match>"));
+            }
+            let source: usize = fields[1].parse().map_err(|_| err("source channel"))?;
+            let destination: usize = fields[2].parse().map_err(|_| err("destination channel"))?;
+            if source == destination {
+                return Err(err("source and destination are the same channel"));
+            }
+            let id_match = Self::parse_match(fields[3]).ok_or_else(|| err("ID match"))?;
+            let mut rule = GatewayRule {
+                source,
+                destination,
+                id_match,
+                rewrite_id: None,
+                operations: Vec::default(),
+                rate_limit: None,
+            };
+            for option in &fields[4..] {
+                let (key, value) = option.split_once('=').ok_or_else(|| err(option))?;
+                match key {
+                    "id" => {
+                        let id = Self::parse_hex(value).ok_or_else(|| err(option))?;
+                        if id > EXTENDED_ID_MAX {
+                            return Err(err(option));
+                        }
+                        rule.rewrite_id = Some(id);
+                    }
+                    "rate" => {
+                        let ms: u64 = value.parse().map_err(|_| err(option))?;
+                        rule.rate_limit = Some(Duration::from_millis(ms));
+                    }
+                    "set" | "and" | "or" | "xor" => {
+                        let (index, byte) = value.split_once(':').ok_or_else(|| err(option))?;
+                        let index: usize = index.parse().map_err(|_| err(option))?;
+                        let byte = u8::from_str_radix(byte, 16).map_err(|_| err(option))?;
+                        if index > 7 {
+                            return Err(err(option));
+                        }
+                        rule.operations.push(match key {
+                            "set" => ByteOperation::Set(index, byte),
+                            "and" => ByteOperation::And(index, byte),
+                            "or" => ByteOperation::Or(index, byte),
+                            _ => ByteOperation::Xor(index, byte),
+                        });
+                    }
+                    _ => return Err(err(option)),
+                }
+            }
+            rules.push(rule);
+        }
+        Ok(rules)
+    }
+
+    fn parse_match(field: &str) -> Option<IdMatch> {
+        if field == "*" {
+            return Some(IdMatch::All);
+        }
+        let is_extended = field
+            .split(['/', '-'])
+            .any(|id| id.trim_start_matches("0x").trim_start_matches("0X").len() > 3);
+        let id_max = if is_extended {
+            EXTENDED_ID_MAX
+        } else {
+            STANDARD_ID_MAX
+        };
+        let id_match = if let Some((id, mask)) = field.split_once('/') {
+            IdMatch::Mask(Self::parse_hex(id)?, Self::parse_hex(mask)?, is_extended)
+        } else if let Some((min, max)) = field.split_once('-') {
+            let (min, max) = (Self::parse_hex(min)?, Self::parse_hex(max)?);
+            if min > max {
+                return None;
+            }
+            IdMatch::Range(min, max, is_extended)
+        } else {
+            IdMatch::Mask(Self::parse_hex(field)?, id_max, is_extended)
+        };
+        match id_match {
+            IdMatch::Mask(id, ..) | IdMatch::Range(_, id, _) if id > id_max => None,
+            id_match => Some(id_match),
+        }
+    }
+
+    fn parse_hex(value: &str) -> Option<u32> {
+        let value = value.trim_start_matches("0x").trim_start_matches("0X");
+        u32::from_str_radix(value, 16).ok()
+    }
+
+    fn apply(&self, (is_extended, id, data): &TransmitFrame) -> TransmitFrame {
+        let (is_extended, id) = match self.rewrite_id {
+            Some(new_id) => (*is_extended || new_id > STANDARD_ID_MAX, new_id),
+            None => (*is_extended, *id),
+        };
+        let mut data = data.clone();
+        for operation in &self.operations {
+            operation.apply(&mut data);
+        }
+        (is_extended, id, data)
+    }
+}
+
+enum GatewayCommand {
+    Apply(String),
+    Enable(bool),
+}
+
+pub struct GatewayHandler<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub gateway_rx: Receiver<(usize, TransmitFrame)>,
+    pub gateway_enabled: Arc<AtomicBool>,
+    pub transmit_tx: Vec<Sender<TransmitFrame>>,
+}
+
+impl<'a> GatewayHandler<'a> {
+    pub fn run(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            // The channel numbers may have changed since the rules were applied
+            if ui.get_gateway_routes().row_count() > 0 {
+                ui.set_gateway_routes(ModelRc::default());
+                ui.set_gateway_status(SharedString::from("Apply the rules to the new channels"));
+            }
+            let ui_handle = ui.as_weak();
+            ui.on_gateway_open_rules(move || {
+                if let Some(path) = FileDialog::new()
+                    .add_filter("gateway rules", &["gw", "txt"])
+                    .set_directory("./")
+                    .pick_file()
+                {
+                    match fs::read_to_string(&path) {
+                        Ok(content) => ui_handle.unwrap().set_gateway_rules(content.into()),
+                        Err(e) => println!("ERR: Failed to read {}: {}", path.display(), e),
+                    }
+                }
+            });
+            let tx_clone = tx.clone();
+            ui.on_gateway_apply(move |rules| {
+                let _ = tx_clone.send(GatewayCommand::Apply(rules.to_string()));
+            });
+            ui.on_gateway_enable(move |en| {
+                let _ = tx.send(GatewayCommand::Enable(en));
+            });
+        });
+        let mut rules: Vec<GatewayRule> = Vec::default();
+        let mut counters: Vec<(i32, i32)> = Vec::default();
+        let mut last_forwarded: HashMap<(usize, u32), Instant> = HashMap::default();
+        let mut last_update = Instant::now();
+        loop {
+            while let Ok(command) = rx.try_recv() {
+                match command {
+                    GatewayCommand::Apply(content) => match GatewayRule::parse(&content) {
+                        Ok(new_rules) => {
+                            let status = match new_rules
+                                .iter()
+                                .find(|rule| rule.destination >= self.transmit_tx.len())
+                            {
+                                Some(rule) => {
+                                    format!("Channel {} is not open", rule.destination)
+                                }
+                                None => format!("{} rules applied", new_rules.len()),
+                            };
+                            let routes: Vec<GatewayRoute> = content
+                                .lines()
+                                .map(str::trim)
+                                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                                .enumerate()
+                                .map(|(count, line)| GatewayRoute {
+                                    rule: SharedString::from(line),
+                                    forwarded: 0,
+                                    dropped: 0,
+                                    color: if count % 2 == 0 {
+                                        EVEN_COLOR
+                                    } else {
+                                        ODD_COLOR
+                                    },
+                                })
+                                .collect();
+                            counters = vec![(0, 0); new_rules.len()];
+                            rules = new_rules;
+                            last_forwarded.clear();
+                            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                                ui.set_gateway_status(SharedString::from(status));
+                                ui.set_gateway_routes(Rc::new(VecModel::from(routes)).into());
+                            });
+                        }
+                        Err(e) => {
+                            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                                ui.set_gateway_status(SharedString::from(e));
+                            });
+                        }
+                    },
+                    GatewayCommand::Enable(en) => {
+                        self.gateway_enabled.store(en, Ordering::Relaxed);
+                        // Frames received while disabled are not forwarded
+                        while self.gateway_rx.try_recv().is_ok() {}
+                    }
+                }
+            }
+            match self.gateway_rx.recv_timeout(Duration::from_millis(10)) {
+                Ok((source, frame)) => {
+                    Self::route(
+                        &self.transmit_tx,
+                        source,
+                        &frame,
+                        &rules,
+                        &mut counters,
+                        &mut last_forwarded,
+                    );
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            if last_update.elapsed() >= Duration::from_millis(200) {
+                last_update = Instant::now();
+                let counters = counters.clone();
+                let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                    let routes = ui.get_gateway_routes();
+                    for (index, (forwarded, dropped)) in counters.into_iter().enumerate() {
+                        if let Some(mut route) = routes.row_data(index) {
+                            if route.forwarded != forwarded || route.dropped != dropped {
+                                route.forwarded = forwarded;
+                                route.dropped = dropped;
+                                routes.set_row_data(index, route);
+                            }
+                        }
+                    }
+                });
+            }
+        }
+    }
+
+    fn route(
+        transmit_tx: &[Sender<TransmitFrame>],
+        source: usize,
+        frame: &TransmitFrame,
+        rules: &[GatewayRule],
+        counters: &mut [(i32, i32)],
+        last_forwarded: &mut HashMap<(usize, u32), Instant>,
+    ) {
+        let (is_extended, id, _) = frame;
+        for (index, rule) in rules.iter().enumerate() {
+            if rule.source != source || !rule.id_match.matches(*is_extended, *id) {
+                continue;
+            }
+            if let Some(rate_limit) = rule.rate_limit {
+                let now = Instant::now();
+                match last_forwarded.get(&(index, *id)) {
+                    Some(last) if now.duration_since(*last) < rate_limit => {
+                        counters[index].1 += 1;
+                        continue;
+                    }
+                    _ => {
+                        last_forwarded.insert((index, *id), now);
+                    }
+                }
+            }
+            match transmit_tx.get(rule.destination) {
+                Some(transmit_tx) => {
+                    let _ = transmit_tx.send(rule.apply(frame));
+                    counters[index].0 += 1;
+                }
+                None => counters[index].1 += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(
+        rules: &str,
+        frames: &[(usize, TransmitFrame)],
+    ) -> (Vec<Vec<TransmitFrame>>, Vec<(i32, i32)>) {
+        let rules = GatewayRule::parse(rules).unwrap();
+        let (channels, receivers): (Vec<_>, Vec<_>) = (0..3).map(|_| mpsc::channel()).unzip();
+        let mut counters = vec![(0, 0); rules.len()];
+        let mut last_forwarded = HashMap::default();
+        for (source, frame) in frames {
+            GatewayHandler::route(
+                &channels,
+                *source,
+                frame,
+                &rules,
+                &mut counters,
+                &mut last_forwarded,
+            );
+        }
+        let forwarded = receivers
+            .iter()
+            .map(|receiver| receiver.try_iter().collect())
+            .collect();
+        (forwarded, counters)
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rules = GatewayRule::parse(
+            "# comment\n\nROUTE 0 1 *\nROUTE 1 0 7E8-7EF rate=100\nROUTE 1 0 123 id=321 set=0:55",
+        )
+        .unwrap();
+        assert_eq!(rules.len(), 3);
+        assert_eq!(rules[1].rate_limit, Some(Duration::from_millis(100)));
+        assert_eq!(rules[2].rewrite_id, Some(0x321));
+        assert_eq!(rules[2].operations.len(), 1);
+
+        for invalid in [
+            "ROUTE 0 0 *",
+            "ROUTE 0 1",
+            "FORWARD 0 1 *",
+            "ROUTE 0 1 200-100",
+            "ROUTE 0 1 800",
+            "ROUTE 0 1 123 set=8:00",
+            "ROUTE 0 1 123 id=20000000",
+            "ROUTE 0 1 123 speed=1",
+        ] {
+            assert!(GatewayRule::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn matches_ids_and_frame_format() {
+        let standard = GatewayRule::parse_match("123").unwrap();
+        assert!(standard.matches(false, 0x123));
+        assert!(!standard.matches(true, 0x123));
+        let extended = GatewayRule::parse_match("00000123").unwrap();
+        assert!(extended.matches(true, 0x123));
+        assert!(!extended.matches(false, 0x123));
+        let mask = GatewayRule::parse_match("00FEF100/00FFFF00").unwrap();
+        assert!(mask.matches(true, 0x18FEF1FA));
+        assert!(!mask.matches(true, 0x18FEF2FA));
+        let range = GatewayRule::parse_match("7E8-7EF").unwrap();
+        assert!(range.matches(false, 0x7E8) && range.matches(false, 0x7EF));
+        assert!(!range.matches(false, 0x7F0) && !range.matches(true, 0x7E8));
+        assert!(GatewayRule::parse_match("*")
+            .unwrap()
+            .matches(true, 0x1FFFFFFF));
+    }
+
+    #[test]
+    fn rewrites_id_and_bytes() {
+        let rules = GatewayRule::parse(
+            "ROUTE 0 1 123 id=321 set=0:55 and=1:0F or=2:80 xor=3:FF\nROUTE 0 1 124 id=18FF0001",
+        )
+        .unwrap();
+        assert_eq!(
+            rules[0].apply(&(false, 0x123, vec![0x00, 0xAB, 0x01, 0x0F])),
+            (false, 0x321, vec![0x55, 0x0B, 0x81, 0xF0])
+        );
+        // Operations behind the DLC are ignored
+        assert_eq!(
+            rules[0].apply(&(false, 0x123, vec![0x00])),
+            (false, 0x321, vec![0x55])
+        );
+        // The frame becomes extended if the new ID needs it
+        assert_eq!(
+            rules[1].apply(&(false, 0x124, vec![])),
+            (true, 0x18FF0001, vec![])
+        );
+    }
+
+    #[test]
+    fn routes_matching_frames() {
+        let (forwarded, counters) = route(
+            "ROUTE 0 1 123\nROUTE 0 2 *\nROUTE 0 5 *",
+            &[
+                (0, (false, 0x123, vec![1])),
+                (0, (true, 0x123, vec![2])),
+                (1, (false, 0x123, vec![3])),
+            ],
+        );
+        assert!(forwarded[0].is_empty());
+        assert_eq!(forwarded[1], vec![(false, 0x123, vec![1])]);
+        assert_eq!(forwarded[2].len(), 2);
+        // Channel 5 is not open, its frames are dropped
+        assert_eq!(counters, vec![(1, 0), (2, 0), (0, 2)]);
+    }
+
+    #[test]
+    fn limits_rate_per_id() {
+        let frames: Vec<(usize, TransmitFrame)> = [0x100, 0x100, 0x101, 0x100]
+            .iter()
+            .map(|id| (0, (false, *id, vec![])))
+            .collect();
+        let (forwarded, counters) = route("ROUTE 0 1 * rate=1000", &frames);
+        assert_eq!(
+            forwarded[1],
+            vec![(false, 0x100, vec![]), (false, 0x101, vec![])]
+        );
+        assert_eq!(counters, vec![(2, 2)]);
+    }
+
+    // Unit test of `route` with real frames: the test reads the source and writes the destination
+    // interface itself in place of the channel threads, which need the UI.
+    // Needs two virtual interfaces:
+    // sudo ip link add dev vcan0 type vcan && sudo ip link set up vcan0 (same for vcan1)
+    #[cfg(target_os = "linux")]
+    #[test]
+    #[ignore = "needs the vcan0 and vcan1 interfaces"]
+    fn route_rewrites_vcan_frames() {
+        use socketcan::{CanFrame, CanSocket, EmbeddedFrame, Frame, Socket, StandardId};
+
+        let sender = CanSocket::open("vcan0").unwrap();
+        let source = CanSocket::open("vcan0").unwrap();
+        let destination = CanSocket::open("vcan1").unwrap();
+        let receiver = CanSocket::open("vcan1").unwrap();
+        for socket in [&source, &receiver] {
+            socket.set_read_timeout(Duration::from_secs(1)).unwrap();
+        }
+        let rules = GatewayRule::parse("ROUTE 0 1 123 id=321 xor=0:FF").unwrap();
+        let (transmit_tx, transmit_rx) = mpsc::channel();
+        let channels = vec![mpsc::channel().0, transmit_tx];
+        let mut counters = vec![(0, 0)];
+        let mut last_forwarded = HashMap::default();
+
+        let frame = CanFrame::new(StandardId::new(0x123).unwrap(), &[0x0F, 0x22]).unwrap();
+        sender.write_frame(&frame).unwrap();
+        let received = source.read_frame().unwrap();
+        let frame = (
+            received.is_extended(),
+            received.raw_id(),
+            received.data().to_vec(),
+        );
+        GatewayHandler::route(
+            &channels,
+            0,
+            &frame,
+            &rules,
+            &mut counters,
+            &mut last_forwarded,
+        );
+        let (_, id, data) = transmit_rx.try_recv().unwrap();
+        let frame = CanFrame::new(StandardId::new(id as u16).unwrap(), &data).unwrap();
+        destination.write_frame(&frame).unwrap();
+
+        let routed = receiver.read_frame().unwrap();
+        assert_eq!(routed.raw_id(), 0x321);
+        assert_eq!(routed.data(), &[0xF0, 0x22]);
+    }
+}
@@ -0,0 +1,222 @@
+use slint::{Model, ModelNotify, ModelTracker, SharedString, VecModel};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use crate::slint_generatedAppWindow::{IdTraceRow, TraceByte};
+
+use super::time_format::{TimeFormat, MAX_TIME_PRECISION};
+use super::trace::{TraceEntry, TraceModel};
+use super::{EVEN_COLOR, ODD_COLOR};
+
+struct IdTraceEntry {
+    entry: TraceEntry,
+    count: i32,
+    period: Option<f64>,
+    changed: u64,
+}
+
+impl IdTraceEntry {
+    fn key(entry: &TraceEntry) -> (usize, bool, u32) {
+        (entry.channel, entry.is_extended, entry.id)
+    }
+}
+
+pub struct IdTraceModel {
+    rows: RefCell<Vec<IdTraceEntry>>,
+    precision: Cell<u32>,
+    notify: ModelNotify,
+}
+
+impl Default for IdTraceModel {
+    fn default() -> Self {
+        Self {
+            rows: RefCell::default(),
+            precision: Cell::new(MAX_TIME_PRECISION),
+            notify: ModelNotify::default(),
+        }
+    }
+}
+
+impl IdTraceModel {
+    pub fn update(&self, entries: &[TraceEntry]) {
+        for entry in entries.iter().filter(|entry| !entry.is_error) {
+            let mut rows = self.rows.borrow_mut();
+            let key = IdTraceEntry::key(entry);
+            match rows.binary_search_by_key(&key, |row| IdTraceEntry::key(&row.entry)) {
+                Ok(index) => {
+                    let row = &mut rows[index];
+                    let period = (entry.time - row.entry.time)
+                        .num_microseconds()
+                        .unwrap_or_default() as f64
+                        / 1000.0;
+                    // Bytes added by a longer frame count as changed
+                    row.changed = (0..(entry.len as usize).min(entry.data.len()))
+                        .filter(|&byte| {
+                            byte >= row.entry.len as usize
+                                || row.entry.data.get(byte) != Some(&entry.data[byte])
+                        })
+                        .fold(0, |changed, byte| changed | 1 << byte);
+                    row.entry = entry.clone();
+                    row.count += 1;
+                    row.period = Some(period);
+                    drop(rows);
+                    self.notify.row_changed(index);
+                }
+                Err(index) => {
+                    rows.insert(
+                        index,
+                        IdTraceEntry {
+                            entry: entry.clone(),
+                            count: 1,
+                            period: None,
+                            changed: 0,
+                        },
+                    );
+                    drop(rows);
+                    self.notify.row_added(index, 1);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        self.rows.borrow_mut().clear();
+        self.notify.reset();
+    }
+
+    pub fn set_time_format(&self, format: TimeFormat) {
+        self.precision.set(format.precision);
+        self.notify.reset();
+    }
+}
+
+impl Model for IdTraceModel {
+    type Data = IdTraceRow;
+
+    fn row_count(&self) -> usize {
+        self.rows.borrow().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        let rows = self.rows.borrow();
+        let id_entry = rows.get(row)?;
+        let entry = &id_entry.entry;
+        let raw = TraceModel::row(entry, String::new(), false);
+        let data = &entry.data[..(entry.len as usize).min(entry.data.len())];
+        let bytes: Vec<TraceByte> = data
+            .iter()
+            .enumerate()
+            .map(|(count, byte)| TraceByte {
+                text: SharedString::from(format!("{:02X}", byte)),
+                changed: id_entry.changed & (1 << count) != 0,
+            })
+            .collect();
+        Some(IdTraceRow {
+            channel: raw.channel,
+            id: raw.id,
+            len: raw.len,
+            data: Rc::new(VecModel::from(bytes)).into(),
+            count: id_entry.count,
+            period: match id_entry.period {
+                Some(period) => TimeFormat::period(self.precision.get(), period).into(),
+                None => SharedString::from("-"),
+            },
+            color: if row % 2 == 0 { EVEN_COLOR } else { ODD_COLOR },
+        })
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_handler::time_format::TimeMode;
+    use chrono::{Local, TimeDelta, TimeZone};
+
+    fn entry(ms: i64, channel: usize, id: u32, data: &[u8]) -> TraceEntry {
+        TraceEntry {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+                + TimeDelta::milliseconds(ms),
+            channel,
+            id,
+            is_extended: id > 0x7FF,
+            is_error: false,
+            len: data.len() as u8,
+            data: data.to_vec(),
+            previous: None,
+        }
+    }
+
+    fn changed(model: &IdTraceModel, row: usize) -> Vec<bool> {
+        let data = model.row_data(row).unwrap().data;
+        (0..data.row_count())
+            .map(|byte| data.row_data(byte).unwrap().changed)
+            .collect()
+    }
+
+    #[test]
+    fn marks_changed_bytes() {
+        let model = IdTraceModel::default();
+        model.update(&[entry(0, 0, 0x100, &[1, 2, 3])]);
+        assert_eq!(changed(&model, 0), [false, false, false]);
+        model.update(&[entry(10, 0, 0x100, &[1, 5, 3])]);
+        assert_eq!(changed(&model, 0), [false, true, false]);
+        // Only compared with the previous frame
+        model.update(&[entry(20, 0, 0x100, &[1, 5, 3])]);
+        assert_eq!(changed(&model, 0), [false, false, false]);
+        // Bytes added by a longer frame count as changed
+        model.update(&[entry(30, 0, 0x100, &[1, 5, 3, 0])]);
+        assert_eq!(changed(&model, 0), [false, false, false, true]);
+        model.update(&[entry(40, 0, 0x100, &[9, 5])]);
+        assert_eq!(changed(&model, 0), [true, false]);
+        let row = model.row_data(0).unwrap();
+        assert_eq!(row.count, 5);
+        assert_eq!(row.len, 2);
+        assert_eq!(row.data.row_data(0).unwrap().text, "09");
+    }
+
+    #[test]
+    fn keeps_one_row_per_id() {
+        let model = IdTraceModel::default();
+        let mut error = entry(5, 0, 0x004, &[0; 8]);
+        error.is_error = true;
+        model.update(&[
+            entry(0, 1, 0x123, &[1]),
+            entry(0, 0, 0x18FF1234, &[1]),
+            entry(0, 0, 0x200, &[1]),
+            error,
+            entry(12, 0, 0x200, &[2]),
+        ]);
+        // Sorted by channel, standard IDs before extended IDs, error frames are left out
+        let rows: Vec<(String, String)> = (0..model.row_count())
+            .map(|row| {
+                let row = model.row_data(row).unwrap();
+                (row.channel.to_string(), row.id.to_string())
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                ("CH0".to_string(), "0x200".to_string()),
+                ("CH0".to_string(), "0x18FF1234".to_string()),
+                ("CH1".to_string(), "0x123".to_string()),
+            ]
+        );
+        assert_eq!(model.row_data(0).unwrap().period, "12.000 ms");
+        assert_eq!(model.row_data(1).unwrap().period, "-");
+        model.set_time_format(TimeFormat::new(TimeMode::Absolute, 4));
+        assert_eq!(model.row_data(0).unwrap().period, "12.0 ms");
+        model.clear();
+        assert_eq!(model.row_count(), 0);
+    }
+}
@@ -1,10 +1,13 @@
-use crate::slint_generatedAppWindow::{socket_info, AppWindow};
+use crate::slint_generatedAppWindow::{socket_info, AppWindow, CanChannel, ChannelOptions};
 #[cfg(target_os = "windows")]
 use pcan_basic::hw::attached_channels as available_interfaces;
-use slint::{ModelRc, SharedString, VecModel, Weak};
+use slint::{Model, ModelRc, SharedString, VecModel, Weak};
 #[cfg(target_os = "linux")]
 use socketcan::available_interfaces;
-use std::time::Duration;
+use std::{rc::Rc, time::Duration};
+
+use super::backend::{check_backend, is_backend};
+use super::slcan::SLCAN_PREFIX;
 pub struct Init<'a> {
     pub ui_handle: &'a Weak<AppWindow>,
 }
@@ -15,7 +18,8 @@ impl<'a> Init<'a> {
         loop {
             match available_interfaces() {
                 Ok(interface) => {
-                    if interface.is_empty() {
+                    let serial_ports = Self::serial_ports();
+                    if interface.is_empty() && serial_ports.is_empty() {
                         let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
                             let socket_info = socket_info {
                                 index: ModelRc::new(VecModel::from(Vec::default())),
@@ -26,6 +30,9 @@ impl<'a> Init<'a> {
                         });
                     } else {
                         #[cfg(target_os = "linux")]
+                        let interface: Vec<String> =
+                            interface.into_iter().chain(serial_ports).collect();
+                        #[cfg(target_os = "linux")]
                         if previous_interfaces != interface {
                             let interface_clone = interface.clone();
                             previous_interfaces = interface.clone();
@@ -64,6 +71,12 @@ impl<'a> Init<'a> {
                                     .push(channel.channel_information.channel_handle as i32);
                                 count += 1;
                             }
+                            for serial_port in serial_ports {
+                                interface_names.push(SharedString::from(serial_port));
+                                // Serial adapters are opened by name, not by PCAN handle
+                                interface_index.push(-1);
+                                count += 1;
+                            }
                             if previous_interfaces != interface_names {
                                 previous_interfaces = interface_names.clone();
                                 let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
@@ -98,4 +111,84 @@ impl<'a> Init<'a> {
             std::thread::sleep(Duration::from_millis(50));
         }
     }
+
+    pub fn add_channel(
+        &self,
+        name: SharedString,
+        index: i32,
+        bitrate: SharedString,
+        data_bitrate: SharedString,
+        options: ChannelOptions,
+    ) -> Result<(), String> {
+        let ui = self.ui_handle.unwrap();
+        let mut channels: Vec<CanChannel> = ui.get_channels().iter().collect();
+        if name.is_empty() {
+            return Ok(());
+        }
+        // Addresses typed on the init page must not reach the interface setup unchecked
+        if is_backend(&name) {
+            check_backend(&name)?;
+        } else if !ui
+            .get_can_sockets()
+            .name
+            .iter()
+            .any(|socket| socket == name)
+        {
+            return Err(format!("Unknown interface {}", name));
+        }
+        if let Some(channel) = channels.iter_mut().find(|channel| channel.name == name) {
+            // Keep the DBC messages of the channel
+            channel.index = index;
+            channel.bitrate = bitrate;
+            channel.data_bitrate = data_bitrate;
+            channel.options = options;
+            ui.set_channels(Rc::new(VecModel::from(channels)).into());
+            return Ok(());
+        }
+        channels.push(CanChannel {
+            name,
+            index,
+            bitrate,
+            data_bitrate,
+            options,
+            state: SharedString::from("-"),
+            bus_load: 0,
+            peak_load: 0,
+            worst_case_load: 0,
+            tec: -1,
+            rec: -1,
+            load_plot: SharedString::default(),
+            messages: ModelRc::default(),
+            unknown_ids: ModelRc::default(),
+        });
+        let channel_names: Vec<SharedString> = channels
+            .iter()
+            .enumerate()
+            .map(|(index, channel)| SharedString::from(format!("CH{} {}", index, channel.name)))
+            .collect();
+        ui.set_channel_names(Rc::new(VecModel::from(channel_names)).into());
+        ui.set_channels(Rc::new(VecModel::from(channels)).into());
+        Ok(())
+    }
+
+    pub fn clear_channels(&self) {
+        let ui = self.ui_handle.unwrap();
+        ui.set_channel_names(ModelRc::default());
+        ui.set_channels(ModelRc::default());
+        ui.set_selected_channel(0);
+        ui.set_messages(ModelRc::default());
+        ui.set_is_filter(false);
+        ui.set_filter_messages(ModelRc::default());
+    }
+
+    fn serial_ports() -> Vec<String> {
+        serialport::available_ports()
+            .map(|ports| {
+                ports
+                    .into_iter()
+                    .map(|port| format!("{}{}", SLCAN_PREFIX, port.port_name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
 }
@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+/// Minimal ISO 15765-2 (ISO-TP) receiver for classic CAN frames.
+pub struct IsoTpReceiver {
+    buffer: Vec<u8>,
+    expected_len: usize,
+    next_sequence: u8,
+    last_frame: Instant,
+}
+
+pub enum IsoTpEvent {
+    Complete(Vec<u8>),
+    FlowControl([u8; 8]),
+    Pending,
+    Invalid,
+}
+
+const N_CR_TIMEOUT: Duration = Duration::from_millis(1000);
+
+impl Default for IsoTpReceiver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IsoTpReceiver {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::default(),
+            expected_len: 0,
+            next_sequence: 0,
+            last_frame: Instant::now(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+        self.expected_len = 0;
+        self.next_sequence = 0;
+    }
+
+    pub fn feed(&mut self, data: &[u8]) -> IsoTpEvent {
+        if data.is_empty() {
+            return IsoTpEvent::Invalid;
+        }
+        if self.expected_len > 0 && self.last_frame.elapsed() > N_CR_TIMEOUT {
+            // The previous transfer timed out, drop it
+            self.reset();
+        }
+        self.last_frame = Instant::now();
+        match data[0] >> 4 {
+            // Single frame
+            0x0 => {
+                let len = (data[0] & 0x0F) as usize;
+                if len == 0 || len > data.len() - 1 {
+                    return IsoTpEvent::Invalid;
+                }
+                self.reset();
+                IsoTpEvent::Complete(data[1..=len].to_vec())
+            }
+            // First frame
+            0x1 => {
+                if data.len() < 8 {
+                    return IsoTpEvent::Invalid;
+                }
+                self.reset();
+                self.expected_len = (((data[0] & 0x0F) as usize) << 8) | data[1] as usize;
+                self.buffer.extend_from_slice(&data[2..]);
+                self.next_sequence = 1;
+                // Continue to send, block size 0 (no limit), separation time 0 ms
+                IsoTpEvent::FlowControl([0x30, 0x00, 0x00, 0x55, 0x55, 0x55, 0x55, 0x55])
+            }
+            // Consecutive frame
+            0x2 => {
+                if self.expected_len == 0 || data[0] & 0x0F != self.next_sequence {
+                    self.reset();
+                    return IsoTpEvent::Invalid;
+                }
+                self.next_sequence = (self.next_sequence + 1) & 0x0F;
+                self.buffer.extend_from_slice(&data[1..]);
+                if self.buffer.len() >= self.expected_len {
+                    self.buffer.truncate(self.expected_len);
+                    let payload = std::mem::take(&mut self.buffer);
+                    self.reset();
+                    IsoTpEvent::Complete(payload)
+                } else {
+                    IsoTpEvent::Pending
+                }
+            }
+            _ => IsoTpEvent::Invalid,
+        }
+    }
+
+    /// Build a single frame for payloads up to 7 bytes, padded with 0x55.
+    pub fn single_frame(payload: &[u8]) -> Option<[u8; 8]> {
+        if payload.is_empty() || payload.len() > 7 {
+            return None;
+        }
+        let mut frame = [0x55; 8];
+        frame[0] = payload.len() as u8;
+        frame[1..=payload.len()].copy_from_slice(payload);
+        Some(frame)
+    }
+}
@@ -1,17 +1,60 @@
+pub(crate) mod acceptance_filter;
+pub(crate) mod auto_baud;
+pub(crate) mod backend;
+pub(crate) mod bus_load;
 pub(crate) mod can_handler;
+pub(crate) mod cannelloni;
+pub(crate) mod cycle_monitor;
 pub(crate) mod dbc_file;
+pub(crate) mod dbc_table;
 pub(crate) mod debug;
+#[cfg(target_os = "linux")]
+pub(crate) mod error_frame;
+pub(crate) mod expression;
 pub(crate) mod filter;
+pub(crate) mod gateway;
+pub(crate) mod id_trace;
 pub(crate) mod init;
+pub(crate) mod isotp;
+pub(crate) mod n2k;
+pub(crate) mod obd;
+pub(crate) mod signal_updates;
+pub(crate) mod simulated;
+pub(crate) mod slcan;
+pub(crate) mod socketcand;
+pub(crate) mod statistics;
+pub(crate) mod time_format;
+pub(crate) mod timestamp;
+pub(crate) mod trace;
+pub(crate) mod trigger;
+pub(crate) mod unknown_ids;
+pub(crate) mod xcp;
 
-pub use can_handler::CanHandler;
+pub use acceptance_filter::AcceptanceFilter;
+pub use auto_baud::AutoBaud;
+pub use backend::is_backend;
+pub use can_handler::{CanHandler, ChannelCommand};
+pub use cycle_monitor::{CycleMonitor, DEFAULT_TIMEOUT_FACTOR};
 pub use dbc_file::DBCFile;
+pub use dbc_table::DbcTable;
 pub use debug::DebugHandler;
+pub use expression::{ChannelDbcs, FilterExpression};
 pub use filter::PacketFilter;
+pub use gateway::GatewayHandler;
+pub use id_trace::IdTraceModel;
 pub use init::Init;
+pub use n2k::{N2kDecoder, PgnDefinition, PgnFile};
+pub use obd::ObdHandler;
 #[cfg(target_os = "windows")]
 use pcan_basic::socket::Baudrate;
+pub use signal_updates::SignalUpdates;
 use slint::Color;
+pub use statistics::StatisticsHandler;
+pub use time_format::{TimeFormat, TimeMode};
+pub use trace::TraceModel;
+pub use trigger::TriggerHandler;
+pub use unknown_ids::UnknownIds;
+pub use xcp::{XcpHandler, XCP_NO_ID};
 
 const ODD_COLOR: Color = Color::from_rgb_u8(0x18, 0x1c, 0x27);
 const EVEN_COLOR: Color = Color::from_rgb_u8(0x13, 0x16, 0x1f);
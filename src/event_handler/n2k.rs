@@ -0,0 +1,449 @@
+use rfd::FileDialog;
+use slint::{ModelRc, SharedString, VecModel, Weak};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+
+use crate::slint_generatedAppWindow::{AppWindow, CanData, CanSignal};
+
+use super::cycle_monitor::CYCLE_OK;
+use super::{EVEN_COLOR, ODD_COLOR};
+
+#[derive(Clone)]
+pub struct PgnSignal {
+    pub name: String,
+    pub start_bit: usize,
+    pub bit_len: usize,
+    pub is_signed: bool,
+    pub factor: f64,
+    pub offset: f64,
+    pub unit: String,
+}
+
+#[derive(Clone)]
+pub struct PgnDefinition {
+    pub pgn: u32,
+    pub name: String,
+    pub is_fast_packet: bool,
+    pub signals: Vec<PgnSignal>,
+}
+
+impl PgnDefinition {
+    /// Signals are little endian as defined by NMEA 2000.
+    pub fn parse(content: &str) -> Result<Vec<PgnDefinition>, String> {
+        let mut definitions: Vec<PgnDefinition> = Vec::new();
+        for (line_count, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let err = || {
+                format!(
+                    "Invalid PGN definition at line {}: {}",
+                    line_count + 1,
+                    line
+                )
+            };
+            match fields[0] {
+                "PGN" if fields.len() >= 4 => definitions.push(PgnDefinition {
+                    pgn: fields[1].parse().map_err(|_| err())?,
+                    is_fast_packet: match fields[2] {
+                        "fast" => true,
+                        "single" => false,
+                        _ => return Err(err()),
+                    },
+                    name: fields[3..].join(" "),
+                    signals: Vec::default(),
+                }),
+                "SIG" if fields.len() >= 7 => {
+                    let definition = definitions.last_mut().ok_or_else(err)?;
+                    let bit_len: usize = fields[3].parse().map_err(|_| err())?;
+                    if bit_len == 0 || bit_len > 64 {
+                        return Err(err());
+                    }
+                    definition.signals.push(PgnSignal {
+                        name: fields[1].to_string(),
+                        start_bit: fields[2].parse().map_err(|_| err())?,
+                        bit_len,
+                        is_signed: fields[4] == "s",
+                        factor: fields[5].parse().map_err(|_| err())?,
+                        offset: fields[6].parse().map_err(|_| err())?,
+                        unit: fields.get(7).unwrap_or(&"").to_string(),
+                    });
+                }
+                _ => return Err(err()),
+            }
+        }
+        Ok(definitions)
+    }
+
+    /// Decode all signals, values with all bits set are "data not available".
+    fn decode(&self, data: &[u8]) -> HashMap<String, String> {
+        let mut signal_data = HashMap::new();
+        for signal in &self.signals {
+            if signal.start_bit + signal.bit_len > data.len() * 8 {
+                continue;
+            }
+            let mut raw: u64 = 0;
+            for bit in 0..signal.bit_len {
+                let position = signal.start_bit + bit;
+                if data[position / 8] & (1 << (position % 8)) != 0 {
+                    raw |= 1 << bit;
+                }
+            }
+            let max = if signal.bit_len == 64 {
+                u64::MAX
+            } else {
+                (1 << signal.bit_len) - 1
+            };
+            let is_not_available = if signal.is_signed {
+                raw == max >> 1
+            } else {
+                raw == max
+            };
+            let value = if is_not_available && signal.bit_len > 1 {
+                "N/A".to_string()
+            } else {
+                let value = if signal.is_signed && signal.bit_len < 64 {
+                    // Sign extend
+                    let shift = 64 - signal.bit_len;
+                    ((raw << shift) as i64 >> shift) as f64
+                } else if signal.is_signed {
+                    raw as i64 as f64
+                } else {
+                    raw as f64
+                };
+                format!("{}", value * signal.factor + signal.offset)
+            };
+            signal_data.insert(signal.name.clone(), value);
+        }
+        signal_data
+    }
+}
+
+struct FastPacket {
+    sequence: u8,
+    next_frame: u8,
+    len: usize,
+    data: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct N2kDecoder {
+    definitions: HashMap<u32, PgnDefinition>,
+    fast_packets: HashMap<(u8, u32), FastPacket>,
+}
+
+impl N2kDecoder {
+    pub fn new(definitions: Vec<PgnDefinition>) -> Self {
+        Self {
+            definitions: definitions
+                .into_iter()
+                .map(|definition| (definition.pgn, definition))
+                .collect(),
+            fast_packets: HashMap::default(),
+        }
+    }
+
+    pub fn definitions(&self) -> Vec<PgnDefinition> {
+        self.definitions.values().cloned().collect()
+    }
+
+    /// Split a 29-bit identifier into (PGN, source address).
+    pub fn pgn_from_id(id: u32) -> (u32, u8) {
+        let pdu_format = (id >> 16) & 0xFF;
+        let pgn = if pdu_format < 240 {
+            // PDU1: destination specific, PS is the destination address
+            (id >> 8) & 0x3FF00
+        } else {
+            (id >> 8) & 0x3FFFF
+        };
+        (pgn, (id & 0xFF) as u8)
+    }
+
+    pub fn decode(&mut self, id: u32, data: &[u8]) -> Option<(u32, HashMap<String, String>)> {
+        let (pgn, source) = Self::pgn_from_id(id);
+        let definition = self.definitions.get(&pgn)?;
+        if !definition.is_fast_packet {
+            return Some((pgn, definition.decode(data)));
+        }
+        // Fast-packet: byte 0 = sequence counter (3 bits) | frame counter (5 bits)
+        let sequence = data.first()? >> 5;
+        let frame = data[0] & 0x1F;
+        if frame == 0 {
+            let len = *data.get(1)? as usize;
+            let mut packet = FastPacket {
+                sequence,
+                next_frame: 1,
+                len,
+                data: data[2..].to_vec(),
+            };
+            if packet.data.len() >= len {
+                packet.data.truncate(len);
+                return Some((pgn, definition.decode(&packet.data)));
+            }
+            self.fast_packets.insert((source, pgn), packet);
+            return None;
+        }
+        let packet = self.fast_packets.get_mut(&(source, pgn))?;
+        if packet.sequence != sequence || packet.next_frame != frame {
+            // Lost a frame, drop the message
+            self.fast_packets.remove(&(source, pgn));
+            return None;
+        }
+        packet.next_frame += 1;
+        packet.data.extend_from_slice(&data[1..]);
+        if packet.data.len() >= packet.len {
+            let mut packet = self.fast_packets.remove(&(source, pgn))?;
+            packet.data.truncate(packet.len);
+            return Some((pgn, definition.decode(&packet.data)));
+        }
+        None
+    }
+}
+
+pub struct PgnFile<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub n2k_tx: &'a [Sender<Vec<PgnDefinition>>],
+}
+
+impl<'a> PgnFile<'a> {
+    pub fn process_pgn_file(&self) {
+        let Some(path) = Self::open_pgn_file() else {
+            return;
+        };
+        let definitions = match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| PgnDefinition::parse(&content))
+        {
+            Ok(definitions) => definitions,
+            Err(e) => {
+                println!("ERR: Failed to read PGN file {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let ui = self.ui_handle.unwrap();
+        let message_vec: Vec<CanData> = definitions
+            .iter()
+            .enumerate()
+            .map(|(message_count, definition)| {
+                let can_signals: Vec<CanSignal> = definition
+                    .signals
+                    .iter()
+                    .map(|signal| CanSignal {
+                        signal_name: SharedString::from(signal.name.as_str()),
+                        signal_value: SharedString::from("0"),
+                        factor: SharedString::from(signal.factor.to_string()),
+                        unit: SharedString::from(signal.unit.as_str()),
+                    })
+                    .collect();
+                CanData {
+                    can_id: SharedString::from(format!("PGN {}", definition.pgn)),
+                    packet_name: SharedString::from(definition.name.as_str()),
+                    signal_value: ModelRc::new(VecModel::from(can_signals)),
+                    counter: 0,
+                    raw_can: SharedString::from(""),
+                    color: if message_count % 2 == 0 {
+                        EVEN_COLOR
+                    } else {
+                        ODD_COLOR
+                    },
+                    circle_time: "0.0".into(),
+                    time_stamp: "0".into(),
+                    cycle_time: 0.0,
+                    cycle_state: CYCLE_OK,
+                    timeouts: 0,
+                }
+            })
+            .collect();
+        ui.set_n2k_messages(Rc::new(VecModel::from(message_vec)).into());
+        for n2k_tx in self.n2k_tx {
+            let _ = n2k_tx.send(definitions.clone());
+        }
+    }
+
+    fn open_pgn_file() -> Option<PathBuf> {
+        FileDialog::new()
+            .add_filter("pgn", &["pgn"])
+            .set_directory("./")
+            .pick_file()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PGN_FILE: &str = include_str!("../../pgn/nmea2000.pgn");
+
+    fn id(pgn: u32, source: u8) -> u32 {
+        // Priority 2
+        0x0800_0000 | (pgn << 8) | source as u32
+    }
+
+    fn fast_frames(sequence: u8, payload: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = vec![[
+            vec![sequence << 5, payload.len() as u8],
+            payload[..6].to_vec(),
+        ]
+        .concat()];
+        for (frame, chunk) in payload[6..].chunks(7).enumerate() {
+            let mut data = [vec![(sequence << 5) | (frame as u8 + 1)], chunk.to_vec()].concat();
+            data.resize(8, 0xFF);
+            frames.push(data);
+        }
+        frames
+    }
+
+    fn engine_dynamic() -> Vec<u8> {
+        vec![
+            0x01, // Instance
+            0xA0, 0x0F, // Oil_Pressure 4000
+            0xFF, 0xFF, // Oil_Temperature N/A
+            0xFF, 0xFF, // Temperature N/A
+            0xFF, 0x7F, // Alternator_Potential N/A
+            0xFB, 0xFF, // Fuel_Rate -5
+            0x10, 0x0E, 0x00, 0x00, // Total_Engine_Hours 3600
+            0xFF, 0xFF, // Coolant_Pressure N/A
+            0x64, 0x00, // Fuel_Pressure 100
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // Reserved
+            0x32, // Engine_Load 50
+            0xF6, // Engine_Torque -10
+        ]
+    }
+
+    #[test]
+    fn parses_pgn_file() {
+        let definitions = PgnDefinition::parse(PGN_FILE).unwrap();
+        let pgns: Vec<u32> = definitions.iter().map(|d| d.pgn).collect();
+        assert_eq!(
+            pgns,
+            [127250, 127488, 127489, 127508, 129025, 129026, 129029, 130306]
+        );
+        let engine = &definitions[2];
+        assert_eq!(engine.name, "Engine Parameters, Dynamic");
+        assert!(engine.is_fast_packet);
+        assert!(!definitions[0].is_fast_packet);
+        assert_eq!(engine.signals.len(), 11);
+        let temperature = &engine.signals[2];
+        assert_eq!(temperature.name, "Oil_Temperature");
+        assert_eq!((temperature.start_bit, temperature.bit_len), (24, 16));
+        assert!(!temperature.is_signed);
+        assert_eq!((temperature.factor, temperature.offset), (0.1, -273.15));
+        assert_eq!(temperature.unit, "degC");
+        assert!(engine.signals[4].is_signed);
+        assert_eq!(engine.signals[0].unit, "");
+
+        assert!(PgnDefinition::parse("SIG SID 0 8 u 1 0").is_err());
+        assert!(PgnDefinition::parse("PGN 127250 multi Heading").is_err());
+        assert_eq!(
+            PgnDefinition::parse("PGN 127250 single Heading\nSIG SID 0 0 u 1 0").err(),
+            Some("Invalid PGN definition at line 2: SIG SID 0 0 u 1 0".to_string())
+        );
+    }
+
+    #[test]
+    fn splits_identifier() {
+        assert_eq!(N2kDecoder::pgn_from_id(0x09F1_0D23), (127245, 0x23));
+        // PDU1: the destination address is not part of the PGN
+        assert_eq!(N2kDecoder::pgn_from_id(0x18EA_FF00), (59904, 0x00));
+    }
+
+    #[test]
+    fn reassembles_fast_packets() {
+        let mut decoder = N2kDecoder::new(PgnDefinition::parse(PGN_FILE).unwrap());
+        let frames = fast_frames(3, &engine_dynamic());
+        assert_eq!(frames.len(), 4);
+        for (count, data) in frames[..3].iter().enumerate() {
+            assert!(decoder.decode(id(127489, 0x23), data).is_none());
+            // Another source sending the same PGN in between
+            if count == 1 {
+                let other = fast_frames(5, &engine_dynamic());
+                assert!(decoder.decode(id(127489, 0x24), &other[0]).is_none());
+            }
+        }
+        let (pgn, values) = decoder.decode(id(127489, 0x23), &frames[3]).unwrap();
+        assert_eq!(pgn, 127489);
+        assert_eq!(values["Instance"], "1");
+        assert_eq!(values["Oil_Pressure"], "400000");
+        assert_eq!(values["Oil_Temperature"], "N/A");
+        assert_eq!(values["Alternator_Potential"], "N/A");
+        assert_eq!(values["Fuel_Rate"], "-0.5");
+        assert_eq!(values["Total_Engine_Hours"], "3600");
+        assert_eq!(values["Fuel_Pressure"], "100000");
+        assert_eq!(values["Engine_Load"], "50");
+        assert_eq!(values["Engine_Torque"], "-10");
+        // The message is complete, further frames start nothing
+        assert!(decoder.decode(id(127489, 0x23), &frames[1]).is_none());
+
+        // A message which fits into the first frame
+        let (_, values) = decoder
+            .decode(
+                id(127489, 0x23),
+                &[0x00, 0x01, 0x07, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            )
+            .unwrap();
+        assert_eq!(values.len(), 1);
+        assert_eq!(values["Instance"], "7");
+    }
+
+    #[test]
+    fn drops_incomplete_fast_packets() {
+        let mut decoder = N2kDecoder::new(PgnDefinition::parse(PGN_FILE).unwrap());
+        let frames = fast_frames(1, &engine_dynamic());
+        // Frame 1 lost
+        assert!(decoder.decode(id(127489, 0x23), &frames[0]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[2]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[1]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[3]).is_none());
+        // Frame of another message sequence
+        let other = fast_frames(2, &engine_dynamic());
+        assert!(decoder.decode(id(127489, 0x23), &frames[0]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &other[1]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[1]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[2]).is_none());
+        assert!(decoder.decode(id(127489, 0x23), &frames[3]).is_none());
+        // The next first frame starts over
+        for data in &other[..3] {
+            assert!(decoder.decode(id(127489, 0x23), data).is_none());
+        }
+        assert!(decoder.decode(id(127489, 0x23), &other[3]).is_some());
+    }
+
+    #[test]
+    fn shows_not_available_values() {
+        let mut decoder = N2kDecoder::new(PgnDefinition::parse(PGN_FILE).unwrap());
+        // Battery Status: Instance 0xFF, Voltage 0x7FFF, Current 0xFFFF, Temperature 0xFFFF
+        let (_, values) = decoder
+            .decode(
+                id(127508, 0x10),
+                &[0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+            )
+            .unwrap();
+        assert_eq!(values["Instance"], "N/A");
+        assert_eq!(values["Voltage"], "N/A");
+        // All bits set is -1 for signed values, only the maximum is "not available"
+        assert_eq!(values["Current"], "-0.1");
+        assert_eq!(values["Temperature"], "N/A");
+        // Vessel Heading: a 2 bit field with both bits set
+        let (_, values) = decoder
+            .decode(
+                id(127250, 0x10),
+                &[0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0xFF],
+            )
+            .unwrap();
+        assert_eq!(values["SID"], "0");
+        assert_eq!(values["Heading"], "0");
+        assert_eq!(
+            values["Deviation"],
+            format!("{}", -32768.0 * 0.0057295779513)
+        );
+        assert_eq!(values["Reference"], "N/A");
+        // Signals beyond the received data are left out
+        let (_, values) = decoder.decode(id(127250, 0x10), &[0x01, 0x02]).unwrap();
+        assert_eq!(values.len(), 1);
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    rc::Rc,
+    sync::mpsc::{self, Receiver, Sender},
+    time::{Duration, Instant},
+};
+
+use slint::{Model, SharedString, VecModel, Weak};
+
+use super::isotp::{IsoTpEvent, IsoTpReceiver};
+use crate::slint_generatedAppWindow::{AppWindow, ObdDtc, ObdPid};
+
+/// Functional (broadcast) request ID for OBD-II on 11-bit CAN.
+pub const OBD_FUNCTIONAL_ID: u32 = 0x7DF;
+/// Response IDs of the ECUs: 0x7E8 - 0x7EF.
+pub const OBD_RESPONSE_IDS: std::ops::RangeInclusive<u32> = 0x7E8..=0x7EF;
+const OBD_RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+const OBD_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+struct PidDefinition {
+    pid: u8,
+    name: &'static str,
+    unit: &'static str,
+    // Data bytes after the service and PID byte
+    bytes: usize,
+    decode: fn(&[u8]) -> f32,
+}
+
+impl PidDefinition {
+    // Response: <0x41> <PID> <data bytes>
+    fn decode_response(&self, response: &[u8]) -> Option<f32> {
+        if response.len() < 2 + self.bytes || response[1] != self.pid {
+            return None;
+        }
+        Some((self.decode)(&response[2..]))
+    }
+}
+
+// SAE J1979 Mode 01 PIDs, A/B are the first/second data bytes of the response
+const PIDS: [PidDefinition; 19] = [
+    PidDefinition {
+        pid: 0x04,
+        name: "Calculated engine load",
+        unit: "%",
+        bytes: 1,
+        decode: |d| d[0] as f32 * 100.0 / 255.0,
+    },
+    PidDefinition {
+        pid: 0x05,
+        name: "Engine coolant temperature",
+        unit: "°C",
+        bytes: 1,
+        decode: |d| d[0] as f32 - 40.0,
+    },
+    PidDefinition {
+        pid: 0x06,
+        name: "Short term fuel trim - Bank 1",
+        unit: "%",
+        bytes: 1,
+        decode: |d| d[0] as f32 / 1.28 - 100.0,
+    },
+    PidDefinition {
+        pid: 0x07,
+        name: "Long term fuel trim - Bank 1",
+        unit: "%",
+        bytes: 1,
+        decode: |d| d[0] as f32 / 1.28 - 100.0,
+    },
+    PidDefinition {
+        pid: 0x0A,
+        name: "Fuel pressure",
+        unit: "kPa",
+        bytes: 1,
+        decode: |d| d[0] as f32 * 3.0,
+    },
+    PidDefinition {
+        pid: 0x0B,
+        name: "Intake manifold absolute pressure",
+        unit: "kPa",
+        bytes: 1,
+        decode: |d| d[0] as f32,
+    },
+    PidDefinition {
+        pid: 0x0C,
+        name: "Engine speed",
+        unit: "rpm",
+        bytes: 2,
+        decode: |d| word(d) / 4.0,
+    },
+    PidDefinition {
+        pid: 0x0D,
+        name: "Vehicle speed",
+        unit: "km/h",
+        bytes: 1,
+        decode: |d| d[0] as f32,
+    },
+    PidDefinition {
+        pid: 0x0E,
+        name: "Timing advance",
+        unit: "°",
+        bytes: 1,
+        decode: |d| d[0] as f32 / 2.0 - 64.0,
+    },
+    PidDefinition {
+        pid: 0x0F,
+        name: "Intake air temperature",
+        unit: "°C",
+        bytes: 1,
+        decode: |d| d[0] as f32 - 40.0,
+    },
+    PidDefinition {
+        pid: 0x10,
+        name: "Mass air flow rate",
+        unit: "g/s",
+        bytes: 2,
+        decode: |d| word(d) / 100.0,
+    },
+    PidDefinition {
+        pid: 0x11,
+        name: "Throttle position",
+        unit: "%",
+        bytes: 1,
+        decode: |d| d[0] as f32 * 100.0 / 255.0,
+    },
+    PidDefinition {
+        pid: 0x1F,
+        name: "Run time since engine start",
+        unit: "s",
+        bytes: 2,
+        decode: word,
+    },
+    PidDefinition {
+        pid: 0x21,
+        name: "Distance traveled with MIL on",
+        unit: "km",
+        bytes: 2,
+        decode: word,
+    },
+    PidDefinition {
+        pid: 0x2F,
+        name: "Fuel tank level input",
+        unit: "%",
+        bytes: 1,
+        decode: |d| d[0] as f32 * 100.0 / 255.0,
+    },
+    PidDefinition {
+        pid: 0x33,
+        name: "Absolute barometric pressure",
+        unit: "kPa",
+        bytes: 1,
+        decode: |d| d[0] as f32,
+    },
+    PidDefinition {
+        pid: 0x42,
+        name: "Control module voltage",
+        unit: "V",
+        bytes: 2,
+        decode: |d| word(d) / 1000.0,
+    },
+    PidDefinition {
+        pid: 0x46,
+        name: "Ambient air temperature",
+        unit: "°C",
+        bytes: 1,
+        decode: |d| d[0] as f32 - 40.0,
+    },
+    PidDefinition {
+        pid: 0x5C,
+        name: "Engine oil temperature",
+        unit: "°C",
+        bytes: 1,
+        decode: |d| d[0] as f32 - 40.0,
+    },
+];
+
+fn word(data: &[u8]) -> f32 {
+    (((data[0] as u16) << 8) | data[1] as u16) as f32
+}
+
+enum ObdCommand {
+    Poll(bool),
+    SelectPid(usize, bool),
+    Dtc(u8),
+}
+
+pub struct ObdHandler<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub obd_rx: Receiver<(u32, Vec<u8>)>,
+    pub transmit_tx: Sender<(bool, u32, Vec<u8>)>,
+}
+
+impl<'a> ObdHandler<'a> {
+    pub fn run(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let mut selected = [false; PIDS.len()];
+        let mut is_polling = false;
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let obd_pids: Vec<ObdPid> = PIDS
+                .iter()
+                .map(|pid| ObdPid {
+                    pid: SharedString::from(format!("0x{:02X}", pid.pid)),
+                    name: SharedString::from(pid.name),
+                    value: SharedString::from("-"),
+                    unit: SharedString::from(pid.unit),
+                    selected: false,
+                })
+                .collect();
+            ui.set_obd_pids(Rc::new(VecModel::from(obd_pids)).into());
+            let tx_clone = tx.clone();
+            ui.on_obd_poll(move |en| {
+                let _ = tx_clone.send(ObdCommand::Poll(en));
+            });
+            let tx_clone = tx.clone();
+            ui.on_obd_select_pid(move |index, checked| {
+                let _ = tx_clone.send(ObdCommand::SelectPid(index as usize, checked));
+            });
+            ui.on_obd_dtc(move |mode| {
+                let _ = tx.send(ObdCommand::Dtc(mode as u8));
+            });
+        });
+        loop {
+            while let Ok(command) = rx.try_recv() {
+                match command {
+                    ObdCommand::Poll(en) => is_polling = en,
+                    ObdCommand::SelectPid(index, checked) => {
+                        if let Some(pid) = selected.get_mut(index) {
+                            *pid = checked;
+                        }
+                    }
+                    ObdCommand::Dtc(mode) => self.process_dtc(mode),
+                }
+            }
+            if is_polling {
+                for (index, pid) in PIDS.iter().enumerate() {
+                    if !selected[index] {
+                        continue;
+                    }
+                    let value = match self
+                        .request(&[0x01, pid.pid])
+                        .and_then(|response| pid.decode_response(&response))
+                    {
+                        Some(value) => format!("{:.2}", value),
+                        None => "No response".to_string(),
+                    };
+                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                        let obd_pids = ui.get_obd_pids();
+                        if let Some(mut row) = obd_pids.row_data(index) {
+                            row.value = value.into();
+                            obd_pids.set_row_data(index, row);
+                        }
+                    });
+                    std::thread::sleep(OBD_POLL_INTERVAL);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn process_dtc(&mut self, mode: u8) {
+        let response = self.request(&[mode]);
+        let (status, dtcs) = match (mode, response) {
+            (0x04, Some(_)) => ("Diagnostic information cleared".to_string(), Vec::default()),
+            (_, Some(response)) => {
+                // Mode 03/07 on CAN: <service + 0x40> <number of DTCs> <2 bytes per DTC>
+                let dtcs: Vec<ObdDtc> = response
+                    .get(2..)
+                    .unwrap_or_default()
+                    .chunks_exact(2)
+                    .filter(|dtc| dtc[0] != 0 || dtc[1] != 0)
+                    .map(|dtc| ObdDtc {
+                        code: SharedString::from(Self::decode_dtc(dtc[0], dtc[1])),
+                        status: SharedString::from(if mode == 0x03 { "stored" } else { "pending" }),
+                    })
+                    .collect();
+                (format!("Found {} DTCs", dtcs.len()), dtcs)
+            }
+            (_, None) => ("No response from ECU".to_string(), Vec::default()),
+        };
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.set_obd_status(SharedString::from(status));
+            ui.set_obd_dtcs(Rc::new(VecModel::from(dtcs)).into());
+        });
+    }
+
+    fn request(&mut self, payload: &[u8]) -> Option<Vec<u8>> {
+        let frame = IsoTpReceiver::single_frame(payload)?;
+        // Drop stale responses of previous requests
+        while self.obd_rx.try_recv().is_ok() {}
+        let _ = self
+            .transmit_tx
+            .send((false, OBD_FUNCTIONAL_ID, frame.to_vec()));
+
+        let mut receiver = IsoTpReceiver::new();
+        let mut responder = None;
+        let start = Instant::now();
+        while start.elapsed() < OBD_RESPONSE_TIMEOUT {
+            let Ok((id, data)) = self.obd_rx.recv_timeout(OBD_RESPONSE_TIMEOUT) else {
+                break;
+            };
+            // Only assemble the response of the first ECU that answers
+            if *responder.get_or_insert(id) != id {
+                continue;
+            }
+            match receiver.feed(&data) {
+                IsoTpEvent::Complete(response) => {
+                    if response.first() == Some(&(payload[0] + 0x40)) {
+                        return Some(response);
+                    }
+                    // Negative response (0x7F) or response to other service
+                    return None;
+                }
+                IsoTpEvent::FlowControl(flow_control) => {
+                    // Physical request ID of an ECU is its response ID - 8
+                    let _ = self
+                        .transmit_tx
+                        .send((false, id - 8, flow_control.to_vec()));
+                }
+                IsoTpEvent::Pending | IsoTpEvent::Invalid => {}
+            }
+        }
+        None
+    }
+
+    fn decode_dtc(high: u8, low: u8) -> String {
+        let system = match high >> 6 {
+            0 => 'P',
+            1 => 'C',
+            2 => 'B',
+            _ => 'U',
+        };
+        format!(
+            "{}{}{:01X}{:02X}",
+            system,
+            (high >> 4) & 0x03,
+            high & 0x0F,
+            low
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pid(pid: u8) -> &'static PidDefinition {
+        PIDS.iter()
+            .find(|definition| definition.pid == pid)
+            .unwrap()
+    }
+
+    #[test]
+    fn decodes_one_byte_pid() {
+        // Coolant temperature 0x5A - 40 = 50 °C
+        assert_eq!(pid(0x05).decode_response(&[0x41, 0x05, 0x5A]), Some(50.0));
+        assert_eq!(pid(0x0D).decode_response(&[0x41, 0x0D, 0x64]), Some(100.0));
+        assert_eq!(pid(0x05).decode_response(&[0x41, 0x05]), None);
+    }
+
+    #[test]
+    fn decodes_two_byte_pid() {
+        // Engine speed (0x1A * 256 + 0xF8) / 4 = 1726 rpm
+        assert_eq!(
+            pid(0x0C).decode_response(&[0x41, 0x0C, 0x1A, 0xF8]),
+            Some(1726.0)
+        );
+        assert_eq!(pid(0x0C).decode_response(&[0x41, 0x0C, 0x1A]), None);
+        assert_eq!(pid(0x0C).decode_response(&[0x41, 0x0D, 0x1A, 0xF8]), None);
+    }
+}
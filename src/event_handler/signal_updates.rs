@@ -0,0 +1,167 @@
+use chrono::{DateTime, Local};
+use std::{collections::HashMap, fmt::Display, time::Duration};
+
+pub const UI_REFRESH_INTERVAL: Duration = Duration::from_millis(33);
+
+pub struct MessageUpdate {
+    pub signals: HashMap<String, String>,
+    pub raw_can: String,
+    pub row: Option<usize>,
+    pub frames: i32,
+    pub period: Option<f32>,
+    pub max_period: Option<f32>,
+    pub time_stamp: i64,
+}
+
+#[derive(Default)]
+pub struct SignalUpdates {
+    pending: HashMap<String, MessageUpdate>,
+    last_seen: HashMap<String, DateTime<Local>>,
+}
+
+impl SignalUpdates {
+    pub fn add<T: Display>(
+        &mut self,
+        can_id: &str,
+        row: Option<usize>,
+        time: DateTime<Local>,
+        signals: HashMap<String, T>,
+        raw_can: String,
+    ) {
+        // The IDs are only allocated for the first frame of a message
+        let period = match self.last_seen.get_mut(can_id) {
+            Some(last_seen) => {
+                let period = (time - *last_seen).num_microseconds().unwrap_or_default();
+                *last_seen = time;
+                Some(period as f32 / 1000.0)
+            }
+            None => {
+                self.last_seen.insert(can_id.to_string(), time);
+                None
+            }
+        };
+        if !self.pending.contains_key(can_id) {
+            self.pending.insert(
+                can_id.to_string(),
+                MessageUpdate {
+                    signals: HashMap::default(),
+                    raw_can: String::new(),
+                    row,
+                    frames: 0,
+                    period: None,
+                    max_period: None,
+                    time_stamp: 0,
+                },
+            );
+        }
+        let Some(update) = self.pending.get_mut(can_id) else {
+            return;
+        };
+        update.signals = signals
+            .into_iter()
+            .map(|(name, value)| (name, value.to_string()))
+            .collect();
+        update.raw_can = raw_can;
+        update.frames += 1;
+        update.period = period.or(update.period);
+        update.max_period = match (update.max_period, period) {
+            (Some(max_period), Some(period)) => Some(max_period.max(period)),
+            (max_period, period) => max_period.or(period),
+        };
+        update.time_stamp = time.timestamp_micros();
+    }
+
+    pub fn take(&mut self) -> Option<HashMap<String, MessageUpdate>> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        Some(std::mem::take(&mut self.pending))
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.last_seen.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_handler::backend::{BackendEvent, CanBackend};
+    use crate::event_handler::dbc_table::{MessageLayout, SignalLayout};
+    use crate::event_handler::simulated::SimulatedBus;
+    use std::time::Instant;
+
+    // Work of a receive thread per frame: decode, collect per ID and hand over the refreshes.
+    // Returns the frames, the time spent on them and the elapsed time.
+    fn receive(bus: &str, duration: Duration) -> (u32, Duration, Duration) {
+        let messages: HashMap<u32, MessageLayout> = (0..4)
+            .map(|index| {
+                let signals = (0..4)
+                    .filter_map(|signal| {
+                        let name = format!("signal_{}", signal);
+                        SignalLayout::new(&name, signal * 16, 16, true, false, 0.1, 0.0)
+                    })
+                    .collect();
+                let name = format!("message_{}", index);
+                let layout = MessageLayout::new(&name, 0x100 + index, index as usize, signals);
+                (0x100 + index, layout)
+            })
+            .collect();
+        let (bitrate, _) = bus.split_once('/').unwrap();
+        let mut bus = SimulatedBus::open(bus, bitrate.parse().unwrap()).unwrap();
+        let mut updates = SignalUpdates::default();
+        let (mut frames, mut busy) = (0, Duration::ZERO);
+        let start = Instant::now();
+        let mut last_refresh = start;
+        while start.elapsed() < duration {
+            let Some(BackendEvent::Frame(frame)) = bus.read_event().unwrap() else {
+                std::thread::yield_now();
+                continue;
+            };
+            let work = Instant::now();
+            if let Some(message) = messages.get(&frame.id) {
+                let raw_can = format!("{:02X?}", frame.data);
+                let signals = message.decode(&frame.data);
+                updates.add(
+                    &message.can_id,
+                    Some(message.row),
+                    Local::now(),
+                    signals,
+                    raw_can,
+                );
+            }
+            if last_refresh.elapsed() >= UI_REFRESH_INTERVAL {
+                last_refresh = Instant::now();
+                let _ = updates.take();
+            }
+            busy += work.elapsed();
+            frames += 1;
+        }
+        (frames, busy, start.elapsed())
+    }
+
+    // cargo test --release high_bus_load -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn high_bus_load_benchmark() {
+        let (frames, busy, elapsed) = receive("1000000/8000", Duration::from_secs(2));
+        let rate = frames as f64 / elapsed.as_secs_f64();
+        let load = busy.as_secs_f64() / elapsed.as_secs_f64() * 100.0;
+        println!(
+            "sim://1000000/8000: {:.0} frames/s, {:.2} µs per frame, {:.1}% of one core",
+            rate,
+            busy.as_secs_f64() * 1e6 / frames as f64,
+            load
+        );
+        assert!(rate > 7900.0);
+        assert!(load < 10.0);
+
+        // Without pacing the bus delivers the frames as fast as they are read
+        let (frames, _, elapsed) = receive("1000000/100000000", Duration::from_secs(1));
+        println!(
+            "Maximum: {:.0} frames/s",
+            frames as f64 / elapsed.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use std::{io, time::Instant};
+
+use super::backend::{BackendEvent, CanBackend, RawFrame};
+
+pub const SIMULATED_PREFIX: &str = "sim://";
+
+const DEFAULT_FRAME_RATE: u32 = 100;
+const CORRUPTED_FRAME_RATE: u32 = 10;
+
+pub struct SimulatedBus {
+    bus_bitrate: u32,
+    bitrate: u32,
+    frame_rate: u32,
+    start: Instant,
+    events: u64,
+    counter: u32,
+}
+
+impl SimulatedBus {
+    pub fn open(bus: &str, bitrate: u32) -> Result<Self, String> {
+        let (bus_bitrate, frame_rate) = Self::parse(bus)?;
+        Ok(Self {
+            bus_bitrate,
+            bitrate,
+            frame_rate,
+            start: Instant::now(),
+            events: 0,
+            counter: 0,
+        })
+    }
+
+    pub fn parse(bus: &str) -> Result<(u32, u32), String> {
+        let (bus_bitrate, frame_rate) = match bus.split_once('/') {
+            Some((bus_bitrate, frame_rate)) => (bus_bitrate, Some(frame_rate)),
+            None => (bus, None),
+        };
+        let bus_bitrate = bus_bitrate
+            .parse()
+            .map_err(|_| format!("Invalid simulated bitrate {}", bus_bitrate))?;
+        let frame_rate = match frame_rate {
+            Some(frame_rate) => frame_rate
+                .parse()
+                .ok()
+                .filter(|frame_rate| *frame_rate > 0)
+                .ok_or(format!("Invalid simulated frame rate {}", frame_rate))?,
+            None => DEFAULT_FRAME_RATE,
+        };
+        Ok((bus_bitrate, frame_rate))
+    }
+}
+
+impl CanBackend for SimulatedBus {
+    fn read_event(&mut self) -> io::Result<Option<BackendEvent>> {
+        // The frames which are due are delivered back to back, like a burst on a busy bus
+        let due = self.start.elapsed().as_secs_f64() * self.frame_rate as f64;
+        if self.events as f64 >= due {
+            return Ok(None);
+        }
+        self.events += 1;
+        self.counter = self.counter.wrapping_add(1);
+        if self.bitrate != self.bus_bitrate && !self.counter.is_multiple_of(CORRUPTED_FRAME_RATE) {
+            return Ok(Some(BackendEvent::Error("stuff error".to_string())));
+        }
+        let data = self.counter.to_le_bytes().to_vec();
+        Ok(Some(BackendEvent::Frame(RawFrame {
+            id: 0x100 + self.counter % 4,
+            is_extended: false,
+            is_remote: false,
+            is_fd: false,
+            brs: false,
+            data,
+            timestamp: None,
+        })))
+    }
+
+    fn write_frame(&mut self, _is_extended: bool, _id: u32, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use serialport::SerialPort;
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use super::backend::{BackendEvent, CanBackend, RawFrame};
+
+pub const SLCAN_PREFIX: &str = "slcan:";
+
+const SLCAN_BAUDRATE: u32 = 115_200;
+const SLCAN_BELL: u8 = 0x07;
+// The device timestamps count ms from 0 to 59999
+const SLCAN_TIMESTAMP_WRAP: Duration = Duration::from_secs(60);
+
+pub struct SlcanPort {
+    port: Box<dyn SerialPort>,
+    buffer: Vec<u8>,
+    last_timestamp: Option<Duration>,
+    timestamp_base: Duration,
+}
+
+impl SlcanPort {
+    /// Open the adapter, set up the bitrate and open the channel. `<port>#<BTR0><BTR1>`, e.g.
+    /// `/dev/ttyUSB0#031C`, sets the SJA1000 bit timing registers instead of a standard bitrate.
+    pub fn open(
+        path: &str,
+        bitrate: u32,
+        listen_only: bool,
+        timestamps: bool,
+    ) -> Result<Self, String> {
+        let (path, setup) = match Self::split_port(path)? {
+            (path, Some(btr_command)) => (path, btr_command),
+            (path, None) => (
+                path,
+                Self::bitrate_command(bitrate)
+                    .ok_or_else(|| format!("Bitrate {} is not supported by slcan", bitrate))?
+                    .to_string(),
+            ),
+        };
+        let port = serialport::new(path, SLCAN_BAUDRATE)
+            .open()
+            .map_err(|e| e.to_string())?;
+        Self::setup(port, &setup, listen_only, timestamps)
+    }
+
+    fn setup(
+        mut port: Box<dyn SerialPort>,
+        bitrate_command: &str,
+        listen_only: bool,
+        timestamps: bool,
+    ) -> Result<Self, String> {
+        port.set_timeout(Duration::from_millis(1))
+            .map_err(|e| e.to_string())?;
+        let mut slcan = Self {
+            port,
+            buffer: Vec::default(),
+            last_timestamp: None,
+            timestamp_base: Duration::ZERO,
+        };
+        // Close the channel first in case it has been left open, ignore the answer
+        let _ = slcan.command("C");
+        slcan.command(bitrate_command)?;
+        slcan.command(if timestamps { "Z1" } else { "Z0" })?;
+        slcan.command(if listen_only { "L" } else { "O" })?;
+        Ok(slcan)
+    }
+
+    pub fn split_port(path: &str) -> Result<(&str, Option<String>), String> {
+        let (path, btr_command) = match path.split_once('#') {
+            Some((path, btr)) => (path, Some(Self::btr_command(btr)?)),
+            None => (path, None),
+        };
+        if path.is_empty() {
+            return Err(String::from("No serial port given"));
+        }
+        Ok((path, btr_command))
+    }
+
+    /// `s` command with the BTR0 and BTR1 register values in hex.
+    fn btr_command(btr: &str) -> Result<String, String> {
+        if btr.len() != 4 || u16::from_str_radix(btr, 16).is_err() {
+            return Err(format!(
+                "Invalid bit timing {}, expected BTR0BTR1 in hex",
+                btr
+            ));
+        }
+        Ok(format!("s{}", btr.to_uppercase()))
+    }
+
+    /// `S` command for the standard bitrates of the Lawicel protocol.
+    fn bitrate_command(bitrate: u32) -> Option<&'static str> {
+        match bitrate {
+            10_000 => Some("S0"),
+            20_000 => Some("S1"),
+            50_000 => Some("S2"),
+            100_000 => Some("S3"),
+            125_000 => Some("S4"),
+            250_000 => Some("S5"),
+            500_000 => Some("S6"),
+            800_000 => Some("S7"),
+            1_000_000 => Some("S8"),
+            _ => None,
+        }
+    }
+
+    fn command(&mut self, command: &str) -> Result<(), String> {
+        self.port
+            .write_all(format!("{}\r", command).as_bytes())
+            .map_err(|e| e.to_string())?;
+        // Wait for CR (OK) or BELL (error)
+        for _ in 0..100 {
+            let mut byte = [0u8; 1];
+            match self.port.read(&mut byte) {
+                Ok(1) if byte[0] == b'\r' => return Ok(()),
+                Ok(1) if byte[0] == SLCAN_BELL => {
+                    return Err(format!("slcan command {} failed", command))
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        Err(format!("slcan command {} timed out", command))
+    }
+
+    /// Parse `tiiildd..[tttt]`, `Tiiiiiiiildd..[tttt]`, `riiil[tttt]` and `Riiiiiiiil[tttt]`.
+    fn parse_frame(line: &[u8]) -> Option<RawFrame> {
+        let line = std::str::from_utf8(line).ok()?;
+        let (is_extended, is_remote, id_len) = match line.chars().next()? {
+            't' => (false, false, 3),
+            'T' => (true, false, 8),
+            'r' => (false, true, 3),
+            'R' => (true, true, 8),
+            _ => return None,
+        };
+        let id = u32::from_str_radix(line.get(1..1 + id_len)?, 16).ok()?;
+        let len = usize::from_str_radix(line.get(1 + id_len..2 + id_len)?, 16).ok()?;
+        if len > 8 {
+            return None;
+        }
+        let data_start = 2 + id_len;
+        let data_len = if is_remote { 0 } else { len * 2 };
+        let data = (0..data_len)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(line.get(data_start + i..data_start + i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        // Optional device timestamp in ms (0 - 59999) when timestamps are enabled
+        let timestamp = match line.get(data_start + data_len..)? {
+            "" => None,
+            timestamp if timestamp.len() == 4 => {
+                let timestamp = u16::from_str_radix(timestamp, 16).ok()?;
+                Some(Duration::from_millis(timestamp as u64))
+            }
+            _ => return None,
+        };
+        Some(RawFrame {
+            id,
+            is_extended,
+            is_remote,
+            is_fd: false,
+            brs: false,
+            data,
+            timestamp,
+        })
+    }
+
+    /// Device time since the channel was opened, counting the wraps of the timestamp.
+    fn device_time(&mut self, timestamp: Duration) -> Duration {
+        if self.last_timestamp.is_some_and(|last| timestamp < last) {
+            self.timestamp_base += SLCAN_TIMESTAMP_WRAP;
+        }
+        self.last_timestamp = Some(timestamp);
+        self.timestamp_base + timestamp
+    }
+}
+
+impl CanBackend for SlcanPort {
+    fn read_event(&mut self) -> io::Result<Option<BackendEvent>> {
+        loop {
+            if let Some(position) = self
+                .buffer
+                .iter()
+                .position(|&byte| byte == b'\r' || byte == SLCAN_BELL)
+            {
+                let line: Vec<u8> = self.buffer.drain(..=position).collect();
+                if line[position] == SLCAN_BELL {
+                    return Ok(Some(BackendEvent::Error("ERROR".to_string())));
+                }
+                match Self::parse_frame(&line[..position]) {
+                    Some(mut frame) => {
+                        frame.timestamp =
+                            frame.timestamp.map(|timestamp| self.device_time(timestamp));
+                        return Ok(Some(BackendEvent::Frame(frame)));
+                    }
+                    // Answer to a transmit command ("z"/"Z") or unknown line
+                    None => continue,
+                }
+            }
+            let mut buffer = [0u8; 256];
+            match self.port.read(&mut buffer) {
+                Ok(0) => return Ok(None),
+                Ok(len) => self.buffer.extend_from_slice(&buffer[..len]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn write_frame(&mut self, is_extended: bool, id: u32, data: &[u8]) -> io::Result<()> {
+        let mut line = if is_extended {
+            format!("T{:08X}{:01X}", id & 0x1FFFFFFF, data.len())
+        } else {
+            format!("t{:03X}{:01X}", id & 0x7FF, data.len())
+        };
+        for byte in data {
+            let _ = write!(line, "{:02X}", byte);
+        }
+        line.push('\r');
+        self.port.write_all(line.as_bytes())
+    }
+}
+
+impl Drop for SlcanPort {
+    fn drop(&mut self) {
+        let _ = self.port.write_all(b"C\r");
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use serialport::TTYPort;
+    use std::{thread, time::Instant};
+
+    // The emulated device answers every command with CR and returns the commands
+    fn emulate_device(mut device: TTYPort, commands: usize) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            device.set_timeout(Duration::from_millis(10)).unwrap();
+            let mut received = Vec::new();
+            let mut line = String::new();
+            let start = Instant::now();
+            while received.len() < commands && start.elapsed() < Duration::from_secs(2) {
+                let mut byte = [0u8; 1];
+                if let Ok(1) = device.read(&mut byte) {
+                    if byte[0] == b'\r' {
+                        received.push(std::mem::take(&mut line));
+                        device.write_all(b"\r").unwrap();
+                    } else {
+                        line.push(byte[0] as char);
+                    }
+                }
+            }
+            received
+        })
+    }
+
+    fn read_frame(slcan: &mut SlcanPort) -> RawFrame {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if let Some(BackendEvent::Frame(frame)) = slcan.read_event().unwrap() {
+                return frame;
+            }
+        }
+        panic!("no frame received");
+    }
+
+    #[test]
+    fn parses_frames() {
+        let frame = SlcanPort::parse_frame(b"t1232AABB").unwrap();
+        assert_eq!((frame.id, frame.is_extended), (0x123, false));
+        assert_eq!(frame.data, vec![0xAA, 0xBB]);
+        assert_eq!(frame.timestamp, None);
+
+        let frame = SlcanPort::parse_frame(b"T18FF0001101011A").unwrap();
+        assert_eq!((frame.id, frame.is_extended), (0x18FF0001, true));
+        assert_eq!(frame.data, vec![0x01]);
+        assert_eq!(frame.timestamp, Some(Duration::from_millis(0x011A)));
+
+        let frame = SlcanPort::parse_frame(b"r7FF8").unwrap();
+        assert!(frame.is_remote && frame.data.is_empty());
+
+        assert!(SlcanPort::parse_frame(b"t1239").is_none());
+        assert!(SlcanPort::parse_frame(b"t1231AA12").is_none());
+        assert!(SlcanPort::parse_frame(b"z").is_none());
+    }
+
+    #[test]
+    fn checks_bit_timing() {
+        assert_eq!(SlcanPort::btr_command("031c"), Ok("s031C".to_string()));
+        assert!(SlcanPort::btr_command("31C").is_err());
+        assert!(SlcanPort::btr_command("XY1C").is_err());
+    }
+
+    #[test]
+    fn counts_timestamp_wraps() {
+        let (host, _device) = TTYPort::pair().unwrap();
+        let mut slcan = SlcanPort {
+            port: Box::new(host),
+            buffer: Vec::default(),
+            last_timestamp: None,
+            timestamp_base: Duration::ZERO,
+        };
+        assert_eq!(
+            slcan.device_time(Duration::from_millis(59_990)),
+            Duration::from_millis(59_990)
+        );
+        assert_eq!(
+            slcan.device_time(Duration::from_millis(10)),
+            Duration::from_millis(60_010)
+        );
+    }
+
+    #[test]
+    fn opens_and_exchanges_frames() {
+        let (host, mut device) = TTYPort::pair().unwrap();
+        let device_clone = device.try_clone_native().unwrap();
+        let emulator = emulate_device(device_clone, 4);
+        let mut slcan = SlcanPort::setup(Box::new(host), "S6", true, true).unwrap();
+        assert_eq!(emulator.join().unwrap(), vec!["C", "S6", "Z1", "L"]);
+
+        device.write_all(b"t1232AABBEA5F\r").unwrap();
+        let frame = read_frame(&mut slcan);
+        assert_eq!((frame.id, frame.data.clone()), (0x123, vec![0xAA, 0xBB]));
+        assert_eq!(frame.timestamp, Some(Duration::from_millis(0xEA5F)));
+        // The next timestamp is smaller, the counter wrapped
+        device.write_all(b"t1231000010\r").unwrap();
+        let frame = read_frame(&mut slcan);
+        assert_eq!(frame.timestamp, Some(Duration::from_millis(60_016)));
+
+        slcan.write_frame(true, 0x18FF0001, &[0x01, 0x02]).unwrap();
+        slcan.write_frame(false, 0x7FF, &[]).unwrap();
+        device.set_timeout(Duration::from_millis(100)).unwrap();
+        let mut written = [0u8; 21];
+        device.read_exact(&mut written).unwrap();
+        assert_eq!(&written, b"T18FF000120102\rt7FF0\r");
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use super::backend::{host_address, BackendEvent, CanBackend, RawFrame};
+
+pub const SOCKETCAND_PREFIX: &str = "socketcand://";
+
+const SOCKETCAND_PORT: u16 = 29536;
+const SOCKETCAND_CHANNEL: &str = "can0";
+const SOCKETCAND_HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+pub struct SocketcandClient {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl SocketcandClient {
+    pub fn open(address: &str) -> Result<Self, String> {
+        let (host, channel) = Self::parse_address(address)?;
+        let stream = TcpStream::connect(&host).map_err(|e| format!("{}: {}", host, e))?;
+        let _ = stream.set_nodelay(true);
+        stream
+            .set_read_timeout(Some(SOCKETCAND_HANDSHAKE_TIMEOUT))
+            .map_err(|e| e.to_string())?;
+        let mut client = Self {
+            stream,
+            buffer: Vec::default(),
+        };
+        client.expect("hi")?;
+        client.send(&format!("< open {} >", channel))?;
+        client.expect("ok")?;
+        client.send("< rawmode >")?;
+        client.expect("ok")?;
+        client
+            .stream
+            .set_read_timeout(Some(Duration::from_millis(1)))
+            .map_err(|e| e.to_string())?;
+        Ok(client)
+    }
+
+    pub fn parse_address(address: &str) -> Result<(String, &str), String> {
+        let (host, channel) = match address.split_once('/') {
+            Some((host, channel)) => (host, channel),
+            None => (address, SOCKETCAND_CHANNEL),
+        };
+        if channel.is_empty() || channel.contains(char::is_whitespace) {
+            return Err(format!("Invalid socketcand channel {}", channel));
+        }
+        Ok((host_address(host, SOCKETCAND_PORT)?, channel))
+    }
+
+    fn send(&mut self, message: &str) -> Result<(), String> {
+        self.stream
+            .write_all(message.as_bytes())
+            .map_err(|e| e.to_string())
+    }
+
+    fn expect(&mut self, answer: &str) -> Result<(), String> {
+        match self.read_message() {
+            Ok(Some(message)) if message == answer => Ok(()),
+            Ok(Some(message)) => Err(format!(
+                "socketcand: expected < {} >, got < {} >",
+                answer, message
+            )),
+            Ok(None) => Err(format!("socketcand: no answer, expected < {} >", answer)),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Read one `< ... >` message and return its content without the brackets.
+    fn read_message(&mut self) -> io::Result<Option<String>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&byte| byte == b'>') {
+                let message: Vec<u8> = self.buffer.drain(..=end).collect();
+                let message = String::from_utf8_lossy(&message);
+                return match message.find('<') {
+                    Some(start) => Ok(Some(
+                        message[start + 1..message.len() - 1].trim().to_string(),
+                    )),
+                    None => continue,
+                };
+            }
+            let mut buffer = [0u8; 1024];
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "socketcand closed the connection",
+                    ))
+                }
+                Ok(len) => self.buffer.extend_from_slice(&buffer[..len]),
+                Err(e)
+                    if e.kind() == io::ErrorKind::WouldBlock
+                        || e.kind() == io::ErrorKind::TimedOut =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Parse `frame <can_id> <seconds.useconds> [data]*`, data may be split into bytes or not.
+    fn parse_frame(message: &str) -> Option<RawFrame> {
+        let mut fields = message.split_whitespace().skip(1);
+        let id_field = fields.next()?;
+        let id = u32::from_str_radix(id_field, 16).ok()?;
+        let timestamp = fields.next()?;
+        let hex: String = fields.collect();
+        if !hex.len().is_multiple_of(2) || hex.len() > 16 {
+            return None;
+        }
+        let data = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        Some(RawFrame {
+            id,
+            // Extended IDs are always sent with 8 digits
+            is_extended: id_field.len() > 3,
+            is_remote: false,
+            is_fd: false,
+            brs: false,
+            data,
+            timestamp: Self::parse_timestamp(timestamp),
+        })
+    }
+
+    fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+        let (seconds, micros) = timestamp.split_once('.')?;
+        Some(Duration::new(
+            seconds.parse().ok()?,
+            micros.parse::<u32>().ok()?.checked_mul(1000)?,
+        ))
+    }
+}
+
+impl CanBackend for SocketcandClient {
+    fn read_event(&mut self) -> io::Result<Option<BackendEvent>> {
+        loop {
+            let Some(message) = self.read_message()? else {
+                return Ok(None);
+            };
+            if message.starts_with("frame ") {
+                if let Some(frame) = Self::parse_frame(&message) {
+                    return Ok(Some(BackendEvent::Frame(frame)));
+                }
+            } else if message.starts_with("error") {
+                return Ok(Some(BackendEvent::Error(message)));
+            }
+            // < ok > and other answers are ignored
+        }
+    }
+
+    fn write_frame(&mut self, is_extended: bool, id: u32, data: &[u8]) -> io::Result<()> {
+        let id = if is_extended {
+            format!("{:08X}", id & 0x1FFFFFFF)
+        } else {
+            format!("{:03X}", id & 0x7FF)
+        };
+        let data: Vec<String> = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let message = format!("< send {} {} {} >", id, data.len(), data.join(" "));
+        self.stream.write_all(message.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread, time::Instant};
+
+    // Reads one `< ... >` message of the client
+    fn read_command(stream: &mut TcpStream) -> String {
+        let mut message = Vec::new();
+        let mut byte = [0u8; 1];
+        while message.last() != Some(&b'>') {
+            stream.read_exact(&mut byte).unwrap();
+            message.push(byte[0]);
+        }
+        String::from_utf8(message).unwrap()
+    }
+
+    fn read_frame(client: &mut SocketcandClient) -> RawFrame {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(2) {
+            if let Some(BackendEvent::Frame(frame)) = client.read_event().unwrap() {
+                return frame;
+            }
+        }
+        panic!("no frame received");
+    }
+
+    #[test]
+    fn parses_frames() {
+        let frame = SocketcandClient::parse_frame("frame 123 1700000000.000123 AABB").unwrap();
+        assert_eq!((frame.id, frame.is_extended), (0x123, false));
+        assert_eq!(frame.data, vec![0xAA, 0xBB]);
+        assert_eq!(frame.timestamp, Some(Duration::new(1_700_000_000, 123_000)));
+
+        let frame = SocketcandClient::parse_frame("frame 18FF0001 1.5 01 02").unwrap();
+        assert_eq!((frame.id, frame.is_extended), (0x18FF0001, true));
+        assert_eq!(frame.data, vec![0x01, 0x02]);
+
+        assert!(SocketcandClient::parse_frame("frame 123 1.0 ABC").is_none());
+        assert!(SocketcandClient::parse_frame("frame XYZ 1.0").is_none());
+    }
+
+    #[test]
+    fn exchanges_frames_with_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"< hi >").unwrap();
+            assert_eq!(read_command(&mut stream), "< open vcan1 >");
+            stream.write_all(b"< ok >").unwrap();
+            assert_eq!(read_command(&mut stream), "< rawmode >");
+            stream.write_all(b"< ok >").unwrap();
+            stream
+                .write_all(b"< frame 123 1700000000.000123 AABB >< error bus off >")
+                .unwrap();
+            read_command(&mut stream)
+        });
+
+        let mut client = SocketcandClient::open(&format!("127.0.0.1:{}/vcan1", port)).unwrap();
+        let frame = read_frame(&mut client);
+        assert_eq!((frame.id, frame.data.clone()), (0x123, vec![0xAA, 0xBB]));
+        assert!(matches!(
+            client.read_event().unwrap(),
+            Some(BackendEvent::Error(message)) if message == "error bus off"
+        ));
+        client.write_frame(true, 0x18FF0001, &[0x01, 0x02]).unwrap();
+        assert_eq!(server.join().unwrap(), "< send 18FF0001 2 01 02 >");
+    }
+
+    #[test]
+    fn fails_on_wrong_greeting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"< error >").unwrap();
+        });
+        assert!(SocketcandClient::open(&format!("127.0.0.1:{}", port)).is_err());
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use chrono::{DateTime, Local};
+use slint::{SharedString, VecModel, Weak};
+use std::{
+    collections::BTreeMap,
+    rc::Rc,
+    sync::mpsc::{self, Receiver},
+    time::{Duration, Instant},
+};
+
+use crate::slint_generatedAppWindow::{AppWindow, IdStatistic};
+
+use super::{EVEN_COLOR, ODD_COLOR};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct ObservedFrame {
+    pub channel: usize,
+    pub time: DateTime<Local>,
+    pub id: u32,
+    pub is_extended: bool,
+    pub dlc: usize,
+    pub busy: Duration,
+}
+
+struct IdStatistics {
+    count: u64,
+    last_count: u64,
+    first_seen: DateTime<Local>,
+    last_seen: DateTime<Local>,
+    min_period: f64,
+    max_period: f64,
+    period_sum: f64,
+    period_square_sum: f64,
+    dlc: usize,
+    dlc_changes: u64,
+    busy: Duration,
+}
+
+impl IdStatistics {
+    fn new(frame: &ObservedFrame) -> Self {
+        Self {
+            count: 1,
+            last_count: 0,
+            first_seen: frame.time,
+            last_seen: frame.time,
+            min_period: f64::MAX,
+            max_period: 0.0,
+            period_sum: 0.0,
+            period_square_sum: 0.0,
+            dlc: frame.dlc,
+            dlc_changes: 0,
+            busy: frame.busy,
+        }
+    }
+
+    fn update(&mut self, frame: &ObservedFrame) {
+        // Period in ms
+        let period = (frame.time - self.last_seen)
+            .num_microseconds()
+            .unwrap_or_default() as f64
+            / 1000.0;
+        self.count += 1;
+        self.last_seen = frame.time;
+        self.min_period = self.min_period.min(period);
+        self.max_period = self.max_period.max(period);
+        self.period_sum += period;
+        self.period_square_sum += period * period;
+        if frame.dlc != self.dlc {
+            self.dlc = frame.dlc;
+            self.dlc_changes += 1;
+        }
+        self.busy += frame.busy;
+    }
+
+    fn period(&self) -> Option<(f64, f64)> {
+        let periods = (self.count - 1) as f64;
+        if periods < 1.0 {
+            return None;
+        }
+        let average = self.period_sum / periods;
+        let variance = (self.period_square_sum / periods - average * average).max(0.0);
+        Some((average, variance.sqrt()))
+    }
+}
+
+pub struct StatisticsHandler<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub statistics_rx: Receiver<ObservedFrame>,
+}
+
+impl<'a> StatisticsHandler<'a> {
+    pub fn run(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.on_reset_statistics(move || {
+                let _ = tx.send(());
+            });
+        });
+        // Sorted by channel and ID, extended IDs after standard IDs
+        let mut statistics: BTreeMap<(usize, bool, u32), IdStatistics> = BTreeMap::new();
+        let mut channel_busy: BTreeMap<usize, Duration> = BTreeMap::new();
+        let mut last_update = Instant::now();
+        loop {
+            if rx.try_recv().is_ok() {
+                statistics.clear();
+                channel_busy.clear();
+            }
+            while let Ok(frame) = self.statistics_rx.try_recv() {
+                Self::add(&mut statistics, &mut channel_busy, &frame);
+            }
+            let elapsed = last_update.elapsed();
+            if elapsed >= UPDATE_INTERVAL {
+                last_update = Instant::now();
+                let rows = Self::rows(&mut statistics, &channel_busy, elapsed);
+                let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                    ui.set_id_statistics(Rc::new(VecModel::from(rows)).into());
+                });
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn add(
+        statistics: &mut BTreeMap<(usize, bool, u32), IdStatistics>,
+        channel_busy: &mut BTreeMap<usize, Duration>,
+        frame: &ObservedFrame,
+    ) {
+        *channel_busy.entry(frame.channel).or_default() += frame.busy;
+        statistics
+            .entry((frame.channel, frame.is_extended, frame.id))
+            .and_modify(|id_statistics| id_statistics.update(frame))
+            .or_insert_with(|| IdStatistics::new(frame));
+    }
+
+    fn rows(
+        statistics: &mut BTreeMap<(usize, bool, u32), IdStatistics>,
+        channel_busy: &BTreeMap<usize, Duration>,
+        elapsed: Duration,
+    ) -> Vec<IdStatistic> {
+        statistics
+            .iter_mut()
+            .enumerate()
+            .map(|(row, ((channel, is_extended, id), id_statistics))| {
+                let rate =
+                    (id_statistics.count - id_statistics.last_count) as f64 / elapsed.as_secs_f64();
+                id_statistics.last_count = id_statistics.count;
+                let busy = channel_busy
+                    .get(channel)
+                    .map(Duration::as_secs_f64)
+                    .unwrap_or_default();
+                let load_share = if busy > 0.0 {
+                    id_statistics.busy.as_secs_f64() / busy * 100.0
+                } else {
+                    0.0
+                };
+                let (min_period, average_period, max_period, jitter) = match id_statistics.period()
+                {
+                    Some((average, jitter)) => (
+                        format!("{:.2}", id_statistics.min_period),
+                        format!("{:.2}", average),
+                        format!("{:.2}", id_statistics.max_period),
+                        format!("{:.2}", jitter),
+                    ),
+                    None => ("-".into(), "-".into(), "-".into(), "-".into()),
+                };
+                IdStatistic {
+                    channel: SharedString::from(format!("CH{}", channel)),
+                    id: if *is_extended {
+                        SharedString::from(format!("{:08X}", id))
+                    } else {
+                        SharedString::from(format!("{:03X}", id))
+                    },
+                    count: id_statistics.count as i32,
+                    rate: SharedString::from(format!("{:.1}", rate)),
+                    min_period: SharedString::from(min_period),
+                    average_period: SharedString::from(average_period),
+                    max_period: SharedString::from(max_period),
+                    jitter: SharedString::from(jitter),
+                    dlc_changes: id_statistics.dlc_changes as i32,
+                    first_seen: SharedString::from(
+                        id_statistics.first_seen.format("%H:%M:%S%.3f").to_string(),
+                    ),
+                    last_seen: SharedString::from(
+                        id_statistics.last_seen.format("%H:%M:%S%.3f").to_string(),
+                    ),
+                    load_share: SharedString::from(format!("{:.1}", load_share)),
+                    color: if row % 2 == 0 { EVEN_COLOR } else { ODD_COLOR },
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+
+    fn frame(channel: usize, ms: i64, id: u32, dlc: usize, busy_us: u64) -> ObservedFrame {
+        ObservedFrame {
+            channel,
+            time: Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+                + TimeDelta::milliseconds(ms),
+            id,
+            is_extended: id > 0x7FF,
+            dlc,
+            busy: Duration::from_micros(busy_us),
+        }
+    }
+
+    fn rows(frames: &[ObservedFrame]) -> Vec<IdStatistic> {
+        let mut statistics = BTreeMap::new();
+        let mut channel_busy = BTreeMap::new();
+        for frame in frames {
+            StatisticsHandler::add(&mut statistics, &mut channel_busy, frame);
+        }
+        StatisticsHandler::rows(&mut statistics, &channel_busy, Duration::from_secs(2))
+    }
+
+    #[test]
+    fn computes_periods_and_jitter() {
+        // Periods of 10, 20 and 10 ms
+        let rows = rows(&[
+            frame(0, 0, 0x100, 8, 100),
+            frame(0, 10, 0x100, 8, 100),
+            frame(0, 30, 0x100, 8, 100),
+            frame(0, 40, 0x100, 8, 100),
+        ]);
+        let row = &rows[0];
+        assert_eq!(row.count, 4);
+        assert_eq!(row.min_period, "10.00");
+        assert_eq!(row.average_period, "13.33");
+        assert_eq!(row.max_period, "20.00");
+        // sqrt((10² + 20² + 10²) / 3 - 13.33²)
+        assert_eq!(row.jitter, "4.71");
+        assert_eq!(row.first_seen, "12:00:00.000");
+        assert_eq!(row.last_seen, "12:00:00.040");
+    }
+
+    #[test]
+    fn shows_no_period_of_single_frames() {
+        let single = rows(&[frame(0, 0, 0x100, 8, 100)]);
+        assert_eq!(single[0].min_period, "-");
+        assert_eq!(single[0].average_period, "-");
+        assert_eq!(single[0].max_period, "-");
+        assert_eq!(single[0].jitter, "-");
+        // A constant period has no jitter
+        let constant = rows(&[
+            frame(0, 0, 0x100, 8, 100),
+            frame(0, 100, 0x100, 8, 100),
+            frame(0, 200, 0x100, 8, 100),
+        ]);
+        assert_eq!(constant[0].jitter, "0.00");
+    }
+
+    #[test]
+    fn counts_rate_dlc_changes_and_load() {
+        let mut statistics = BTreeMap::new();
+        let mut channel_busy = BTreeMap::new();
+        let frames = [
+            frame(0, 0, 0x18FF1234, 8, 150),
+            frame(0, 0, 0x123, 8, 100),
+            frame(0, 10, 0x123, 4, 100),
+            frame(0, 20, 0x123, 4, 100),
+            frame(0, 30, 0x123, 8, 100),
+            frame(1, 0, 0x123, 8, 100),
+        ];
+        for frame in &frames {
+            StatisticsHandler::add(&mut statistics, &mut channel_busy, frame);
+        }
+        let rows = StatisticsHandler::rows(&mut statistics, &channel_busy, Duration::from_secs(2));
+        // Sorted by channel, standard IDs before extended IDs
+        let ids: Vec<(&str, &str)> = rows
+            .iter()
+            .map(|row| (row.channel.as_str(), row.id.as_str()))
+            .collect();
+        assert_eq!(ids, [("CH0", "123"), ("CH0", "18FF1234"), ("CH1", "123")]);
+        assert_eq!(rows[0].rate, "2.0");
+        assert_eq!(rows[0].dlc_changes, 2);
+        assert_eq!(rows[0].load_share, "72.7");
+        assert_eq!(rows[1].load_share, "27.3");
+        assert_eq!(rows[2].load_share, "100.0");
+        // The rate counts the frames since the last update
+        StatisticsHandler::add(
+            &mut statistics,
+            &mut channel_busy,
+            &frame(0, 40, 0x123, 8, 100),
+        );
+        let rows = StatisticsHandler::rows(&mut statistics, &channel_busy, Duration::from_secs(1));
+        assert_eq!(rows[0].rate, "1.0");
+        assert_eq!(rows[1].rate, "0.0");
+        assert_eq!(rows[0].count, 5);
+    }
+}
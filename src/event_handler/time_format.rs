@@ -0,0 +1,176 @@
+use chrono::{DateTime, Local, TimeDelta};
+
+pub const MAX_TIME_PRECISION: u32 = 6;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum TimeMode {
+    #[default]
+    Absolute,
+    Relative,
+    Delta,
+    DeltaPerId,
+}
+
+impl TimeMode {
+    pub fn from_index(index: i32) -> Self {
+        match index {
+            1 => Self::Relative,
+            2 => Self::Delta,
+            3 => Self::DeltaPerId,
+            _ => Self::Absolute,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TimeFormat {
+    pub mode: TimeMode,
+    pub precision: u32,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        Self {
+            mode: TimeMode::Absolute,
+            precision: MAX_TIME_PRECISION,
+        }
+    }
+}
+
+impl TimeFormat {
+    pub fn new(mode: TimeMode, precision: u32) -> Self {
+        Self {
+            mode,
+            precision: precision.min(MAX_TIME_PRECISION),
+        }
+    }
+
+    pub fn time(
+        &self,
+        time: DateTime<Local>,
+        start: Option<DateTime<Local>>,
+        previous: Option<DateTime<Local>>,
+        previous_id: Option<DateTime<Local>>,
+    ) -> String {
+        let reference = match self.mode {
+            TimeMode::Absolute => return self.absolute(time),
+            TimeMode::Relative => start,
+            TimeMode::Delta => previous,
+            TimeMode::DeltaPerId => previous_id,
+        };
+        match reference {
+            Some(reference) => self.seconds(time - reference),
+            None => String::from("-"),
+        }
+    }
+
+    pub fn absolute(&self, time: DateTime<Local>) -> String {
+        let mut text = time.format("%Y-%m-%d %H:%M:%S").to_string();
+        if self.precision > 0 {
+            let fraction = format!("{:06}", time.timestamp_subsec_micros());
+            text.push('.');
+            text.push_str(&fraction[..self.precision as usize]);
+        }
+        text
+    }
+
+    pub fn seconds(&self, delta: TimeDelta) -> String {
+        let micros = delta.num_microseconds().unwrap_or(i64::MAX);
+        let precision = self.precision as usize;
+        format!("{:.*}", precision, micros as f64 / 1_000_000.0)
+    }
+
+    pub fn period(precision: u32, period: f64) -> String {
+        let precision = precision.min(MAX_TIME_PRECISION).saturating_sub(3) as usize;
+        format!("{:.*} ms", precision, period)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn time(us: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 12, 34, 56).unwrap() + TimeDelta::microseconds(us)
+    }
+
+    fn format(mode: TimeMode, precision: u32) -> TimeFormat {
+        TimeFormat::new(mode, precision)
+    }
+
+    #[test]
+    fn formats_absolute_time() {
+        for precision in 0..=MAX_TIME_PRECISION {
+            let expected = match precision {
+                0 => "2024-05-01 12:34:56".to_string(),
+                digits => format!("2024-05-01 12:34:56.{}", &"987654"[..digits as usize]),
+            };
+            let text = format(TimeMode::Absolute, precision).time(time(987_654), None, None, None);
+            assert_eq!(text, expected);
+        }
+    }
+
+    #[test]
+    fn formats_relative_time() {
+        let expected = ["1", "1.2", "1.23", "1.235", "1.2346", "1.23457", "1.234567"];
+        for (precision, expected) in expected.iter().enumerate() {
+            let format = format(TimeMode::Relative, precision as u32);
+            assert_eq!(
+                format.time(time(1_234_567), Some(time(0)), None, None),
+                *expected
+            );
+        }
+        assert_eq!(
+            format(TimeMode::Relative, 6).time(time(0), None, None, None),
+            "-"
+        );
+    }
+
+    #[test]
+    fn formats_delta_times() {
+        let expected = ["0", "0.2", "0.23", "0.235", "0.2346", "0.23457", "0.234567"];
+        for (precision, expected) in expected.iter().enumerate() {
+            let delta = format(TimeMode::Delta, precision as u32);
+            let delta_per_id = format(TimeMode::DeltaPerId, precision as u32);
+            let start = Some(time(0));
+            // The previous frame at 1 s, the previous frame of the ID at 0.5 s
+            let (previous, previous_id) = (Some(time(1_000_000)), Some(time(500_000)));
+            assert_eq!(
+                delta.time(time(1_234_567), start, previous, previous_id),
+                *expected
+            );
+            assert_eq!(
+                delta_per_id.time(time(734_567), start, previous, previous_id),
+                *expected
+            );
+        }
+        // First frame, first frame of the ID
+        assert_eq!(
+            format(TimeMode::Delta, 3).time(time(0), None, None, None),
+            "-"
+        );
+        assert_eq!(
+            format(TimeMode::DeltaPerId, 3).time(time(0), Some(time(0)), Some(time(0)), None),
+            "-"
+        );
+    }
+
+    #[test]
+    fn formats_periods() {
+        assert_eq!(TimeFormat::period(6, 12.3456), "12.346 ms");
+        assert_eq!(TimeFormat::period(5, 12.3456), "12.35 ms");
+        assert_eq!(TimeFormat::period(4, 12.3456), "12.3 ms");
+        assert_eq!(TimeFormat::period(3, 12.3456), "12 ms");
+        assert_eq!(TimeFormat::period(0, 12.3456), "12 ms");
+        assert_eq!(TimeFormat::period(9, 12.3456), "12.346 ms");
+    }
+
+    #[test]
+    fn limits_precision() {
+        assert_eq!(format(TimeMode::Absolute, 9).precision, MAX_TIME_PRECISION);
+        assert!(TimeMode::from_index(1) == TimeMode::Relative);
+        assert!(TimeMode::from_index(3) == TimeMode::DeltaPerId);
+        assert!(TimeMode::from_index(7) == TimeMode::Absolute);
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Local, TimeDelta};
+#[cfg(target_os = "windows")]
+use pcan_basic::socket::Timestamp;
+#[cfg(target_os = "linux")]
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+const MAX_CLOCK_DIFFERENCE_MS: i64 = 1000;
+
+#[derive(Default)]
+pub struct ReceiveClock {
+    start: Option<DateTime<Local>>,
+}
+
+impl ReceiveClock {
+    pub fn time(&mut self, timestamp: Duration) -> DateTime<Local> {
+        let now = Local::now();
+        let Ok(timestamp) = TimeDelta::from_std(timestamp) else {
+            return now;
+        };
+        if let Some(start) = self.start {
+            let time = start + timestamp;
+            let max_difference = TimeDelta::milliseconds(MAX_CLOCK_DIFFERENCE_MS);
+            if time - now <= max_difference && now - time <= max_difference {
+                return time;
+            }
+        }
+        self.start = Some(now - timestamp);
+        now
+    }
+}
+
+/// Hardware receive time of a PCAN frame since the start of the driver, the 32 bit ms counter
+/// continues in the overflow count.
+#[cfg(target_os = "windows")]
+pub fn pcan_timestamp(timestamp: &Timestamp) -> Duration {
+    let millis = ((timestamp.millis_overflow() as u64) << 32) + timestamp.millis() as u64;
+    Duration::from_millis(millis) + Duration::from_micros(timestamp.micros() as u64)
+}
+
+/// Kernel receive time of the last frame read from the socket (`SIOCGSTAMPNS`), the kernel
+/// enables the timestamps with the first request.
+#[cfg(target_os = "linux")]
+pub fn kernel_time(socket: &impl AsRawFd) -> Option<DateTime<Local>> {
+    const SIOCGSTAMPNS: libc::c_ulong = 0x8907;
+    let mut timestamp = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: the request only writes a timespec to the given pointer
+    let result = unsafe {
+        libc::ioctl(
+            socket.as_raw_fd(),
+            SIOCGSTAMPNS as _,
+            &mut timestamp as *mut libc::timespec,
+        )
+    };
+    if result != 0 || timestamp.tv_sec == 0 {
+        return None;
+    }
+    // time_t and c_long are 32 bit on some targets
+    #[allow(clippy::unnecessary_cast)]
+    let (seconds, nanos) = (timestamp.tv_sec as i64, timestamp.tv_nsec as u32);
+    DateTime::from_timestamp(seconds, nanos).map(|time| time.with_timezone(&Local))
+}
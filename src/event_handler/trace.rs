@@ -0,0 +1,404 @@
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use slint::{Model, ModelNotify, ModelTracker, SharedString};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use crate::slint_generatedAppWindow::raw_can;
+
+#[cfg(target_os = "linux")]
+use super::error_frame::ErrorFrame;
+use super::time_format::{TimeFormat, TimeMode};
+
+const MAX_TRACE_LEN: usize = 2_000_000;
+// Late frames are merged into the newest rows only, older ones are put at the start of this window
+const REORDER_WINDOW: usize = 100_000;
+
+// Channel, error frame, extended ID and ID of the frames the delta per ID refers to
+type TraceKey = (usize, bool, bool, u32);
+
+#[derive(Clone)]
+pub struct TraceEntry {
+    pub time: DateTime<Local>,
+    pub channel: usize,
+    pub id: u32,
+    pub is_extended: bool,
+    pub is_error: bool,
+    pub len: u8,
+    pub data: Vec<u8>,
+    pub previous: Option<DateTime<Local>>,
+}
+
+pub struct TraceModel {
+    max_len: usize,
+    entries: RefCell<VecDeque<TraceEntry>>,
+    search: RefCell<String>,
+    format: Cell<TimeFormat>,
+    start: Cell<Option<DateTime<Local>>>,
+    last_times: RefCell<HashMap<TraceKey, DateTime<Local>>>,
+    notify: ModelNotify,
+}
+
+impl Default for TraceModel {
+    fn default() -> Self {
+        Self::new(MAX_TRACE_LEN)
+    }
+}
+
+impl TraceModel {
+    fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            entries: RefCell::default(),
+            search: RefCell::default(),
+            format: Cell::default(),
+            start: Cell::default(),
+            last_times: RefCell::default(),
+            notify: ModelNotify::default(),
+        }
+    }
+
+    pub fn push(&self, mut new_entries: Vec<TraceEntry>) {
+        let mut last_times = self.last_times.borrow_mut();
+        for entry in &mut new_entries {
+            let key = (entry.channel, entry.is_error, entry.is_extended, entry.id);
+            entry.previous = last_times.insert(key, entry.time);
+        }
+        drop(last_times);
+        let Some(first_time) = new_entries.first().map(|entry| entry.time) else {
+            return;
+        };
+        if self.start.get().is_none() {
+            self.start.set(Some(first_time));
+        }
+        let mut entries = self.entries.borrow_mut();
+        let old_len = entries.len();
+        let added = new_entries.len();
+        // The batches are sorted, frames of another channel which arrive after a later frame
+        // are merged with the newer rows so the trace stays ordered for `find_time`
+        let row = if entries.back().is_some_and(|last| last.time > first_time) {
+            entries
+                .partition_point(|entry| entry.time <= first_time)
+                .max(old_len.saturating_sub(REORDER_WINDOW))
+        } else {
+            old_len
+        };
+        if row < old_len {
+            let mut merged: Vec<TraceEntry> = entries.drain(row..).collect();
+            merged.extend(new_entries);
+            // Both parts are sorted, the stable sort merges the two runs
+            merged.sort_by_key(|entry| entry.time);
+            entries.extend(merged);
+        } else {
+            entries.extend(new_entries);
+        }
+        let removed = entries.len().saturating_sub(self.max_len);
+        entries.drain(..removed);
+        drop(entries);
+        if removed > old_len {
+            self.notify.reset();
+            return;
+        }
+        for changed in row..old_len {
+            self.notify.row_changed(changed);
+        }
+        self.notify.row_added(old_len, added);
+        if removed > 0 {
+            self.notify.row_removed(0, removed);
+        }
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+        self.last_times.borrow_mut().clear();
+        self.start.set(None);
+        self.notify.reset();
+    }
+
+    pub fn set_time_format(&self, format: TimeFormat) {
+        self.format.set(format);
+        self.notify.reset();
+    }
+
+    pub fn search(&self, text: &str, from: i32, forward: bool) -> Option<usize> {
+        let text = text.trim().to_uppercase();
+        if *self.search.borrow() != text {
+            *self.search.borrow_mut() = text.clone();
+            self.notify.reset();
+        }
+        if text.is_empty() {
+            return None;
+        }
+        let entries = self.entries.borrow();
+        let matches = |row: &usize| Self::matches(&entries[*row], &text);
+        if forward {
+            let start = (from + 1).max(0) as usize;
+            (start..entries.len()).find(matches)
+        } else {
+            let end = if from < 0 {
+                entries.len()
+            } else {
+                (from as usize).min(entries.len())
+            };
+            (0..end).rev().find(matches)
+        }
+    }
+
+    pub fn find_time(&self, time: &str) -> Option<usize> {
+        let entries = self.entries.borrow();
+        let time = time.trim();
+        if self.format.get().mode == TimeMode::Relative {
+            let seconds = time.parse::<f64>().ok()?;
+            let offset = TimeDelta::microseconds((seconds * 1_000_000.0) as i64);
+            let target = self.start.get()? + offset;
+            let row = entries.partition_point(|entry| entry.time < target);
+            return (row < entries.len()).then_some(row);
+        }
+        let target = match NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f") {
+            Ok(target) => target,
+            Err(_) => {
+                let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.f").ok()?;
+                entries.back()?.time.date_naive().and_time(time)
+            }
+        };
+        let target = Local.from_local_datetime(&target).earliest()?;
+        let row = entries.partition_point(|entry| entry.time < target);
+        (row < entries.len()).then_some(row)
+    }
+
+    pub fn export(&self, path: &Path) -> Result<usize, String> {
+        let entries = self.entries.borrow();
+        let mut log = String::new();
+        for (row, entry) in entries.iter().enumerate() {
+            let _ = write!(log, "({}) CH{} ", self.time(&entries, row), entry.channel);
+            let data = &entry.data[..(entry.len as usize).min(entry.data.len())];
+            let _ = if entry.is_error {
+                writeln!(log, "ERROR {}", Self::row(entry, String::new(), false).data)
+            } else {
+                let data: String = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+                if entry.is_extended {
+                    writeln!(log, "{:08X}#{}", entry.id, data)
+                } else {
+                    writeln!(log, "{:03X}#{}", entry.id, data)
+                }
+            };
+        }
+        fs::write(path, log).map_err(|e| e.to_string())?;
+        Ok(entries.len())
+    }
+
+    fn matches(entry: &TraceEntry, text: &str) -> bool {
+        let row = Self::row(entry, String::new(), false);
+        row.id.contains(text)
+            || row.data.to_uppercase().contains(text)
+            || row.channel.contains(text)
+    }
+
+    fn time(&self, entries: &VecDeque<TraceEntry>, row: usize) -> String {
+        let entry = &entries[row];
+        self.format.get().time(
+            entry.time,
+            self.start.get(),
+            row.checked_sub(1).map(|previous| entries[previous].time),
+            entry.previous,
+        )
+    }
+
+    pub(super) fn row(entry: &TraceEntry, time: String, highlight: bool) -> raw_can {
+        let data = &entry.data[..(entry.len as usize).min(entry.data.len())];
+        // Error frames are shown decoded instead of their raw data
+        if entry.is_error {
+            #[cfg(target_os = "linux")]
+            let description = ErrorFrame::decode(entry.id, data).description;
+            #[cfg(not(target_os = "linux"))]
+            let description = String::from("error frame");
+            return raw_can {
+                time: SharedString::from(time),
+                channel: SharedString::from(format!("CH{}", entry.channel)),
+                data: SharedString::from(description),
+                id: SharedString::from("ERROR"),
+                len: entry.len as i32,
+                highlight,
+            };
+        }
+        let mut hex = String::new();
+        for (count, byte) in data.iter().enumerate() {
+            let separator = if count == 0 { "" } else { " " };
+            let _ = write!(hex, "{}{:02X}", separator, byte);
+        }
+        raw_can {
+            time: SharedString::from(time),
+            channel: SharedString::from(format!("CH{}", entry.channel)),
+            data: SharedString::from(hex),
+            id: if entry.is_extended {
+                SharedString::from(format!("0x{:08X}", entry.id))
+            } else {
+                SharedString::from(format!("0x{:03X}", entry.id))
+            },
+            len: entry.len as i32,
+            highlight,
+        }
+    }
+}
+
+impl Model for TraceModel {
+    type Data = raw_can;
+
+    fn row_count(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        let entries = self.entries.borrow();
+        let entry = entries.get(row)?;
+        let search = self.search.borrow();
+        let highlight = !search.is_empty() && Self::matches(entry, &search);
+        Some(Self::row(entry, self.time(&entries, row), highlight))
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(ms: i64, channel: usize, id: u32, data: &[u8]) -> TraceEntry {
+        TraceEntry {
+            time: Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+                + TimeDelta::milliseconds(ms),
+            channel,
+            id,
+            is_extended: id > 0x7FF,
+            is_error: false,
+            len: data.len() as u8,
+            data: data.to_vec(),
+            previous: None,
+        }
+    }
+
+    fn times(trace: &TraceModel) -> Vec<i64> {
+        let start = Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        trace
+            .entries
+            .borrow()
+            .iter()
+            .map(|entry| (entry.time - start).num_milliseconds())
+            .collect()
+    }
+
+    #[test]
+    fn keeps_frames_ordered() {
+        let trace = TraceModel::default();
+        trace.push(vec![
+            entry(0, 0, 0x100, &[1]),
+            entry(10, 0, 0x100, &[2]),
+            entry(20, 0, 0x100, &[3]),
+        ]);
+        // A later batch with frames of another channel received before the last frame
+        trace.push(vec![
+            entry(5, 1, 0x200, &[4]),
+            entry(15, 1, 0x200, &[5]),
+            entry(30, 0, 0x100, &[6]),
+        ]);
+        assert_eq!(times(&trace), [0, 5, 10, 15, 20, 30]);
+        trace.push(vec![entry(30, 1, 0x200, &[7]), entry(40, 0, 0x100, &[8])]);
+        assert_eq!(times(&trace), [0, 5, 10, 15, 20, 30, 30, 40]);
+        let entries = trace.entries.borrow();
+        // Frames of the same time keep their order of arrival
+        assert_eq!(entries[5].data, [6]);
+        assert_eq!(entries[6].data, [7]);
+        // The previous frame of the same channel and ID, for the delta per ID
+        assert_eq!(entries[3].previous, Some(entries[1].time));
+        assert_eq!(entries[4].previous, Some(entries[2].time));
+        assert_eq!(entries[0].previous, None);
+        assert_eq!(trace.row_count(), 8);
+    }
+
+    #[test]
+    fn evicts_oldest_frames() {
+        let trace = TraceModel::new(4);
+        trace.push((0..3).map(|ms| entry(ms, 0, 0x100, &[])).collect());
+        trace.push((3..6).map(|ms| entry(ms, 0, 0x100, &[])).collect());
+        assert_eq!(times(&trace), [2, 3, 4, 5]);
+        trace.push((6..16).map(|ms| entry(ms, 0, 0x100, &[])).collect());
+        assert_eq!(times(&trace), [12, 13, 14, 15]);
+        assert_eq!(trace.row_count(), 4);
+        trace.clear();
+        assert_eq!(trace.row_count(), 0);
+    }
+
+    #[test]
+    fn searches_and_highlights() {
+        let trace = TraceModel::default();
+        trace.push(vec![
+            entry(0, 0, 0x123, &[0xDE, 0xAD]),
+            entry(1, 1, 0x456, &[0x01]),
+            entry(2, 0, 0x123, &[0x02]),
+            entry(3, 0, 0x18FF1234, &[0xBE, 0xEF]),
+        ]);
+        assert_eq!(trace.search("123", -1, true), Some(0));
+        assert_eq!(trace.search("123", 0, true), Some(2));
+        assert_eq!(trace.search("123", 2, false), Some(0));
+        assert_eq!(trace.search("123", 0, false), None);
+        // Data is matched as shown, case-insensitive
+        assert_eq!(trace.search("de ad", -1, true), Some(0));
+        assert_eq!(trace.search("ch1", -1, true), Some(1));
+        assert_eq!(trace.search("12", -1, false), Some(3));
+        let highlights: Vec<bool> = (0..4)
+            .map(|row| trace.row_data(row).unwrap().highlight)
+            .collect();
+        assert_eq!(highlights, [true, false, true, true]);
+        assert_eq!(trace.search(" ", -1, true), None);
+        assert!((0..4).all(|row| !trace.row_data(row).unwrap().highlight));
+        let row = trace.row_data(3).unwrap();
+        assert_eq!(row.id, "0x18FF1234");
+        assert_eq!(row.data, "BE EF");
+    }
+
+    #[test]
+    fn finds_time() {
+        let trace = TraceModel::default();
+        trace.push(
+            (0..5)
+                .map(|count| entry(count * 10, 0, 0x100, &[]))
+                .collect(),
+        );
+        assert_eq!(trace.find_time("12:00:00.015"), Some(2));
+        assert_eq!(trace.find_time("2024-05-01 12:00:00.020"), Some(2));
+        assert_eq!(trace.find_time("11:59:59"), Some(0));
+        assert_eq!(trace.find_time("12:00:01"), None);
+        assert_eq!(trace.find_time("noon"), None);
+        trace.set_time_format(TimeFormat::new(TimeMode::Relative, 3));
+        assert_eq!(trace.find_time("0.031"), Some(4));
+        assert_eq!(trace.find_time("12:00:00.015"), None);
+    }
+
+    #[test]
+    fn exports_trace() {
+        let trace = TraceModel::default();
+        trace.push(vec![
+            entry(0, 0, 0x123, &[0xDE, 0xAD]),
+            entry(5, 1, 0x18FF1234, &[]),
+        ]);
+        trace.set_time_format(TimeFormat::new(TimeMode::Relative, 3));
+        let path = std::env::temp_dir().join("can_viewer_trace_export.log");
+        assert_eq!(trace.export(&path), Ok(2));
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "(0.000) CH0 123#DEAD\n(0.005) CH1 18FF1234#\n"
+        );
+        let _ = fs::remove_file(path);
+    }
+}
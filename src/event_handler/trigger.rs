@@ -0,0 +1,531 @@
+use chrono::{DateTime, Local, TimeDelta};
+use rfd::FileDialog;
+use slint::{Model, SharedString, VecModel, Weak};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::slint_generatedAppWindow::{AppWindow, TriggerSettings};
+
+use super::expression::{signal_value, ChannelDbcs, FilterExpression, TraceFrame};
+use super::time_format::TimeFormat;
+
+const CONDITION_EXPRESSION: i32 = 0;
+const CONDITION_ERROR_FRAME: i32 = 1;
+const CONDITION_BUS_OFF: i32 = 2;
+const MAX_BUFFER_LEN: usize = 1_000_000;
+
+pub enum CaptureEvent {
+    Frame {
+        id: u32,
+        is_extended: bool,
+        data: Vec<u8>,
+    },
+    Error(String),
+    State(String),
+}
+
+pub type Capture = (usize, DateTime<Local>, CaptureEvent);
+
+// Channel and frame format/ID of an event, errors and state changes have no ID
+type EventKey = (usize, Option<(bool, u32)>);
+
+enum TriggerCondition {
+    Expression(FilterExpression),
+    ErrorFrame,
+    BusOff,
+}
+
+struct Trigger {
+    condition: TriggerCondition,
+    pre_trigger: TimeDelta,
+    post_trigger: TimeDelta,
+    rearm: bool,
+    folder: PathBuf,
+}
+
+// Events around the trigger, timed by the captures themselves
+#[derive(Default)]
+struct Recorder {
+    buffer: VecDeque<Capture>,
+    // Time and description of the trigger while the post-trigger events are recorded
+    triggered: Option<(DateTime<Local>, String)>,
+    last_match: HashMap<(usize, u32, bool), bool>,
+}
+
+impl Recorder {
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn add(
+        &mut self,
+        trigger: &Trigger,
+        capture: Capture,
+        signal: &dyn Fn(&TraceFrame, &str, &str) -> Option<f64>,
+    ) -> bool {
+        let mut fired = false;
+        if self.triggered.is_none() {
+            self.triggered =
+                TriggerHandler::check(&trigger.condition, &capture, &mut self.last_match, signal)
+                    .map(|description| (capture.1, description));
+            fired = self.triggered.is_some();
+            if !fired {
+                while self
+                    .buffer
+                    .front()
+                    .is_some_and(|(_, time, _)| *time < capture.1 - trigger.pre_trigger)
+                {
+                    self.buffer.pop_front();
+                }
+            }
+        }
+        if self.buffer.len() >= MAX_BUFFER_LEN {
+            self.buffer.pop_front();
+        }
+        self.buffer.push_back(capture);
+        fired
+    }
+
+    fn complete(
+        &self,
+        trigger: &Trigger,
+        now: DateTime<Local>,
+    ) -> Option<&(DateTime<Local>, String)> {
+        self.triggered
+            .as_ref()
+            .filter(|(trigger_time, _)| now >= *trigger_time + trigger.post_trigger)
+    }
+
+    // The buffer and the last matches are kept, a condition which stays true doesn't fire again
+    fn rearm(&mut self) {
+        self.triggered = None;
+    }
+}
+
+enum TriggerCommand {
+    Arm(Trigger),
+    Disarm,
+}
+
+pub struct TriggerHandler<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub capture_rx: Receiver<Capture>,
+    pub capture_enabled: Arc<AtomicBool>,
+    pub dbcs: ChannelDbcs,
+    pub time_format: Arc<Mutex<TimeFormat>>,
+}
+
+impl<'a> TriggerHandler<'a> {
+    pub fn run(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let ui_handle = ui.as_weak();
+            ui.on_trigger_select_folder(move || {
+                if let Some(folder) = FileDialog::new().set_directory("./").pick_folder() {
+                    ui_handle
+                        .unwrap()
+                        .set_trigger_folder(folder.display().to_string().into());
+                }
+            });
+            let ui_handle = ui.as_weak();
+            let tx_clone = tx.clone();
+            ui.on_trigger_arm(move |settings, folder| {
+                let ui = ui_handle.unwrap();
+                match Self::trigger(&settings, &folder) {
+                    Ok(trigger) => {
+                        ui.set_is_trigger_armed(true);
+                        ui.set_trigger_status(SharedString::from("Armed, waiting for the trigger"));
+                        let _ = tx_clone.send(TriggerCommand::Arm(trigger));
+                    }
+                    Err(e) => ui.set_trigger_status(SharedString::from(e)),
+                }
+            });
+            let ui_handle = ui.as_weak();
+            ui.on_trigger_disarm(move || {
+                let ui = ui_handle.unwrap();
+                ui.set_is_trigger_armed(false);
+                ui.set_trigger_status(SharedString::from("Disarmed"));
+                let _ = tx.send(TriggerCommand::Disarm);
+            });
+        });
+        let mut trigger: Option<Trigger> = None;
+        let mut recorder = Recorder::default();
+        let mut captures = 0;
+        loop {
+            while let Ok(command) = rx.try_recv() {
+                recorder.clear();
+                captures = 0;
+                match command {
+                    TriggerCommand::Arm(new_trigger) => {
+                        trigger = Some(new_trigger);
+                        self.capture_enabled.store(true, Ordering::Relaxed);
+                    }
+                    TriggerCommand::Disarm => {
+                        trigger = None;
+                        self.capture_enabled.store(false, Ordering::Relaxed);
+                        while self.capture_rx.try_recv().is_ok() {}
+                    }
+                }
+            }
+            let capture = match self.capture_rx.recv_timeout(Duration::from_millis(10)) {
+                Ok(capture) => Some(capture),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+            let Some(current) = &trigger else {
+                continue;
+            };
+            if let Some(capture) = capture {
+                let dbcs = self.dbcs.lock().unwrap();
+                let signal = |frame: &TraceFrame, message: &str, signal: &str| {
+                    signal_value(&dbcs, frame, message, signal)
+                };
+                if recorder.add(current, capture, &signal) {
+                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                        ui.set_trigger_status(SharedString::from(
+                            "Triggered, recording the post-trigger time",
+                        ));
+                    });
+                }
+            }
+            let Some((trigger_time, description)) = recorder.complete(current, Local::now()) else {
+                continue;
+            };
+            let format = *self.time_format.lock().unwrap();
+            let status = match Self::save(
+                current,
+                &recorder.buffer,
+                *trigger_time,
+                description,
+                format,
+            ) {
+                Ok(path) => {
+                    captures += 1;
+                    let path = path.display().to_string();
+                    let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                        let mut files: Vec<SharedString> = vec![path.into()];
+                        files.extend(ui.get_trigger_captures().iter());
+                        ui.set_trigger_captures(Rc::new(VecModel::from(files)).into());
+                    });
+                    if current.rearm {
+                        format!("{} capture(s) saved, armed again", captures)
+                    } else {
+                        "Capture saved".to_string()
+                    }
+                }
+                Err(e) => format!("Failed to save the capture - {}", e),
+            };
+            recorder.rearm();
+            if !current.rearm {
+                trigger = None;
+                recorder.clear();
+                self.capture_enabled.store(false, Ordering::Relaxed);
+            }
+            let is_armed = trigger.is_some();
+            let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                ui.set_is_trigger_armed(is_armed);
+                ui.set_trigger_status(SharedString::from(status));
+            });
+        }
+    }
+
+    fn trigger(settings: &TriggerSettings, folder: &str) -> Result<Trigger, String> {
+        let condition = match settings.condition {
+            CONDITION_EXPRESSION => {
+                TriggerCondition::Expression(FilterExpression::parse(&settings.expression)?)
+            }
+            CONDITION_ERROR_FRAME => TriggerCondition::ErrorFrame,
+            CONDITION_BUS_OFF => TriggerCondition::BusOff,
+            condition => return Err(format!("Unknown trigger condition {}", condition)),
+        };
+        let folder = PathBuf::from(folder);
+        if !folder.is_dir() {
+            return Err(format!("{} is not a folder", folder.display()));
+        }
+        Ok(Trigger {
+            condition,
+            pre_trigger: TimeDelta::seconds(settings.pre_trigger.max(0) as i64),
+            post_trigger: TimeDelta::seconds(settings.post_trigger.max(0) as i64),
+            rearm: settings.rearm,
+            folder,
+        })
+    }
+
+    fn check(
+        condition: &TriggerCondition,
+        (channel, _, event): &Capture,
+        last_match: &mut HashMap<(usize, u32, bool), bool>,
+        signal: &dyn Fn(&TraceFrame, &str, &str) -> Option<f64>,
+    ) -> Option<String> {
+        match (condition, event) {
+            (
+                TriggerCondition::Expression(expression),
+                CaptureEvent::Frame {
+                    id,
+                    is_extended,
+                    data,
+                },
+            ) => {
+                let frame = TraceFrame {
+                    channel: *channel,
+                    id: *id,
+                    is_extended: *is_extended,
+                    data,
+                };
+                let is_match =
+                    expression.matches(&frame, &|message, name| signal(&frame, message, name));
+                // Only the change to true fires, e.g. a signal crossing its threshold
+                let was_match = last_match.insert((*channel, *id, *is_extended), is_match);
+                (is_match && was_match != Some(true))
+                    .then(|| format!("Expression matched by {:X} on CH{}", id, channel))
+            }
+            (TriggerCondition::ErrorFrame, CaptureEvent::Error(description)) => {
+                Some(format!("Error frame on CH{}: {}", channel, description))
+            }
+            (TriggerCondition::BusOff, CaptureEvent::State(state))
+            | (TriggerCondition::BusOff, CaptureEvent::Error(state))
+                if Self::is_bus_off(state) =>
+            {
+                Some(format!("Bus off on CH{}", channel))
+            }
+            _ => None,
+        }
+    }
+
+    /// The backends name the bus-off state differently: `BUSOFF`, `BusOff`, `bus off`.
+    fn is_bus_off(state: &str) -> bool {
+        state
+            .to_ascii_lowercase()
+            .replace([' ', '_', '-'], "")
+            .contains("busoff")
+    }
+
+    fn save(
+        trigger: &Trigger,
+        buffer: &VecDeque<Capture>,
+        trigger_time: DateTime<Local>,
+        description: &str,
+        format: TimeFormat,
+    ) -> Result<PathBuf, String> {
+        let mut log = String::new();
+        let _ = writeln!(
+            log,
+            "# Trigger: {} at {}",
+            description,
+            format.absolute(trigger_time)
+        );
+        let _ = writeln!(
+            log,
+            "# Pre-trigger {} s, post-trigger {} s",
+            trigger.pre_trigger.num_seconds(),
+            trigger.post_trigger.num_seconds()
+        );
+        let start = trigger_time - trigger.pre_trigger;
+        let end = trigger_time + trigger.post_trigger;
+        // Relative and delta times refer to the events of the file, errors and state changes
+        // count as one ID per channel
+        let mut first = None;
+        let mut previous = None;
+        let mut previous_ids: HashMap<EventKey, DateTime<Local>> = HashMap::default();
+        for (channel, time, event) in buffer {
+            if *time < start || *time > end {
+                continue;
+            }
+            let id = match event {
+                CaptureEvent::Frame {
+                    id, is_extended, ..
+                } => Some((*is_extended, *id)),
+                _ => None,
+            };
+            let previous_id = previous_ids.insert((*channel, id), *time);
+            let _ = write!(
+                log,
+                "({}) CH{} ",
+                format.time(
+                    *time,
+                    Some(*first.get_or_insert(*time)),
+                    previous,
+                    previous_id
+                ),
+                channel
+            );
+            previous = Some(*time);
+            let _ = match event {
+                CaptureEvent::Frame {
+                    id,
+                    is_extended,
+                    data,
+                } => {
+                    let data: String = data.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    if *is_extended {
+                        writeln!(log, "{:08X}#{}", id, data)
+                    } else {
+                        writeln!(log, "{:03X}#{}", id, data)
+                    }
+                }
+                CaptureEvent::Error(description) => writeln!(log, "ERROR {}", description),
+                CaptureEvent::State(state) => writeln!(log, "STATE {}", state),
+            };
+        }
+        let path = trigger.folder.join(format!(
+            "capture_{}.log",
+            trigger_time.format("%Y%m%d_%H%M%S_%3f")
+        ));
+        fs::write(&path, log).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_handler::time_format::TimeMode;
+    use chrono::TimeZone;
+
+    fn time(ms: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap() + TimeDelta::milliseconds(ms)
+    }
+
+    fn frame(ms: i64, id: u32, byte: u8) -> Capture {
+        let event = CaptureEvent::Frame {
+            id,
+            is_extended: false,
+            data: vec![byte],
+        };
+        (0, time(ms), event)
+    }
+
+    fn trigger(expression: &str, rearm: bool) -> Trigger {
+        Trigger {
+            condition: TriggerCondition::Expression(FilterExpression::parse(expression).unwrap()),
+            pre_trigger: TimeDelta::seconds(1),
+            post_trigger: TimeDelta::seconds(2),
+            rearm,
+            folder: std::env::temp_dir(),
+        }
+    }
+
+    fn no_signal(_: &TraceFrame, _: &str, _: &str) -> Option<f64> {
+        None
+    }
+
+    fn times(recorder: &Recorder) -> Vec<i64> {
+        recorder
+            .buffer
+            .iter()
+            .map(|(_, at, _)| (*at - time(0)).num_milliseconds())
+            .collect()
+    }
+
+    #[test]
+    fn trims_pre_trigger_buffer() {
+        let trigger = trigger("data[0] == 1", false);
+        let mut recorder = Recorder::default();
+        for ms in (0..=5000).step_by(500) {
+            assert!(!recorder.add(&trigger, frame(ms, 0x100, 0), &no_signal));
+        }
+        assert_eq!(times(&recorder), [4000, 4500, 5000]);
+        assert!(recorder.complete(&trigger, time(60_000)).is_none());
+    }
+
+    #[test]
+    fn records_post_trigger_time() {
+        let trigger = trigger("data[0] == 1", false);
+        let mut recorder = Recorder::default();
+        for ms in [4000, 4500, 5000] {
+            recorder.add(&trigger, frame(ms, 0x100, 0), &no_signal);
+        }
+        assert!(recorder.add(&trigger, frame(5200, 0x100, 1), &no_signal));
+        // Matches during the post-trigger time don't restart it
+        assert!(!recorder.add(&trigger, frame(6000, 0x200, 1), &no_signal));
+        recorder.add(&trigger, frame(7000, 0x100, 0), &no_signal);
+        assert!(recorder.complete(&trigger, time(7199)).is_none());
+        recorder.add(&trigger, frame(7500, 0x100, 0), &no_signal);
+        let (trigger_time, description) = recorder.complete(&trigger, time(7200)).unwrap();
+        assert_eq!(*trigger_time, time(5200));
+        assert_eq!(description, "Expression matched by 100 on CH0");
+
+        let format = TimeFormat::new(TimeMode::Relative, 1);
+        let path = TriggerHandler::save(
+            &trigger,
+            &recorder.buffer,
+            *trigger_time,
+            description,
+            format,
+        )
+        .unwrap();
+        let log = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(path);
+        // Pre-trigger from 4.2 s, post-trigger until 7.2 s
+        let frames: Vec<&str> = log.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(
+            frames,
+            [
+                "(0.0) CH0 100#00",
+                "(0.5) CH0 100#00",
+                "(0.7) CH0 100#01",
+                "(1.5) CH0 200#01",
+                "(2.5) CH0 100#00",
+            ]
+        );
+    }
+
+    #[test]
+    fn fires_on_becoming_true() {
+        let condition = trigger("data[0] == 1", false).condition;
+        let mut last_match = HashMap::default();
+        let mut check = |capture: Capture| {
+            TriggerHandler::check(&condition, &capture, &mut last_match, &no_signal).is_some()
+        };
+        assert!(check(frame(0, 0x100, 1)));
+        assert!(!check(frame(10, 0x100, 1)));
+        // Each ID has its own edge
+        assert!(check(frame(20, 0x200, 1)));
+        assert!(!check(frame(30, 0x100, 0)));
+        assert!(check(frame(40, 0x100, 1)));
+
+        let mut last_match = HashMap::default();
+        let state = (0, time(0), CaptureEvent::State("BUS_OFF".into()));
+        let error = (1, time(0), CaptureEvent::Error("bus off".into()));
+        let passive = (1, time(0), CaptureEvent::State("ERROR-PASSIVE".into()));
+        let bus_off = TriggerCondition::BusOff;
+        assert!(TriggerHandler::check(&bus_off, &state, &mut last_match, &no_signal).is_some());
+        assert!(TriggerHandler::check(&bus_off, &error, &mut last_match, &no_signal).is_some());
+        assert!(TriggerHandler::check(&bus_off, &passive, &mut last_match, &no_signal).is_none());
+        let error_frame = TriggerCondition::ErrorFrame;
+        assert_eq!(
+            TriggerHandler::check(&error_frame, &error, &mut last_match, &no_signal),
+            Some("Error frame on CH1: bus off".to_string())
+        );
+    }
+
+    #[test]
+    fn rearms_after_capture() {
+        let trigger = trigger("data[0] == 1", true);
+        let mut recorder = Recorder::default();
+        assert!(recorder.add(&trigger, frame(1000, 0x100, 1), &no_signal));
+        recorder.add(&trigger, frame(2000, 0x100, 1), &no_signal);
+        assert!(recorder.complete(&trigger, time(3000)).is_some());
+        recorder.rearm();
+        assert!(recorder.complete(&trigger, time(3000)).is_none());
+        // Still true since the last capture, no new trigger
+        assert!(!recorder.add(&trigger, frame(3500, 0x100, 1), &no_signal));
+        assert!(!recorder.add(&trigger, frame(4000, 0x100, 0), &no_signal));
+        assert!(recorder.add(&trigger, frame(4500, 0x100, 1), &no_signal));
+        // The pre-trigger time of the new capture is kept
+        assert_eq!(times(&recorder), [3500, 4000, 4500]);
+        assert!(recorder.complete(&trigger, time(6499)).is_none());
+        assert!(recorder.complete(&trigger, time(6500)).is_some());
+    }
+}
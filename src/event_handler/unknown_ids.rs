@@ -0,0 +1,156 @@
+use chrono::{DateTime, Local};
+use slint::SharedString;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use crate::slint_generatedAppWindow::UnknownId;
+
+use super::time_format::TimeFormat;
+use super::{EVEN_COLOR, ODD_COLOR};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+
+struct UnknownFrame {
+    data: Vec<u8>,
+    count: i32,
+    last_seen: DateTime<Local>,
+    period: Option<f64>,
+}
+
+pub struct UnknownIds {
+    frames: BTreeMap<(bool, u32), UnknownFrame>,
+    changed: bool,
+    last_update: Instant,
+}
+
+impl Default for UnknownIds {
+    fn default() -> Self {
+        Self {
+            frames: BTreeMap::default(),
+            changed: false,
+            last_update: Instant::now(),
+        }
+    }
+}
+
+impl UnknownIds {
+    pub fn add(&mut self, id: u32, is_extended: bool, data: &[u8], time: DateTime<Local>) {
+        self.changed = true;
+        match self.frames.get_mut(&(is_extended, id)) {
+            Some(frame) => {
+                let period = (time - frame.last_seen)
+                    .num_microseconds()
+                    .unwrap_or_default() as f64
+                    / 1000.0;
+                frame.data = data.to_vec();
+                frame.count += 1;
+                frame.last_seen = time;
+                frame.period = Some(period);
+            }
+            None => {
+                self.frames.insert(
+                    (is_extended, id),
+                    UnknownFrame {
+                        data: data.to_vec(),
+                        count: 1,
+                        last_seen: time,
+                        period: None,
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.changed = true;
+    }
+
+    pub fn rows(&mut self, precision: u32) -> Option<Vec<UnknownId>> {
+        if !self.changed || self.last_update.elapsed() < UPDATE_INTERVAL {
+            return None;
+        }
+        self.changed = false;
+        self.last_update = Instant::now();
+        Some(
+            self.frames
+                .iter()
+                .enumerate()
+                .map(|(row, ((is_extended, id), frame))| {
+                    let mut data = String::with_capacity(frame.data.len() * 3);
+                    for byte in &frame.data {
+                        let _ = write!(data, "{:02X} ", byte);
+                    }
+                    data.pop();
+                    UnknownId {
+                        can_id: if *is_extended {
+                            SharedString::from(format!("{:08X}", id))
+                        } else {
+                            SharedString::from(format!("{:03X}", id))
+                        },
+                        raw_can: data.into(),
+                        counter: frame.count,
+                        circle_time: match frame.period {
+                            Some(period) => TimeFormat::period(precision, period).into(),
+                            None => SharedString::from("-"),
+                        },
+                        color: if row % 2 == 0 { EVEN_COLOR } else { ODD_COLOR },
+                    }
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone};
+
+    fn time(ms: i64) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap() + TimeDelta::milliseconds(ms)
+    }
+
+    fn due_rows(unknown_ids: &mut UnknownIds, precision: u32) -> Option<Vec<UnknownId>> {
+        unknown_ids.last_update = Instant::now() - UPDATE_INTERVAL;
+        unknown_ids.rows(precision)
+    }
+
+    #[test]
+    fn lists_ids_with_period() {
+        let mut unknown_ids = UnknownIds::default();
+        unknown_ids.add(0x123, false, &[0x01], time(0));
+        unknown_ids.add(0x18FF0000, true, &[], time(5));
+        unknown_ids.add(0x100, false, &[0x01, 0x02], time(7));
+        unknown_ids.add(0x123, false, &[0xDE, 0xAD], time(100));
+        let rows = due_rows(&mut unknown_ids, 6).unwrap();
+        // Standard IDs first, sorted by ID
+        let ids: Vec<&str> = rows.iter().map(|row| row.can_id.as_str()).collect();
+        assert_eq!(ids, ["100", "123", "18FF0000"]);
+        assert_eq!(rows[1].raw_can, "DE AD");
+        assert_eq!(rows[1].counter, 2);
+        assert_eq!(rows[1].circle_time, "100.000 ms");
+        assert_eq!(rows[0].circle_time, "-");
+        assert_eq!(rows[2].raw_can, "");
+        unknown_ids.add(0x123, false, &[0xDE, 0xAD], time(112));
+        assert_eq!(
+            due_rows(&mut unknown_ids, 4).unwrap()[1].circle_time,
+            "12.0 ms"
+        );
+    }
+
+    #[test]
+    fn updates_only_after_changes() {
+        let mut unknown_ids = UnknownIds::default();
+        unknown_ids.add(0x123, false, &[0x01], time(0));
+        // Refreshed at most every UPDATE_INTERVAL
+        assert!(unknown_ids.rows(6).is_none());
+        assert_eq!(due_rows(&mut unknown_ids, 6).unwrap().len(), 1);
+        assert!(due_rows(&mut unknown_ids, 6).is_none());
+        unknown_ids.clear();
+        assert!(due_rows(&mut unknown_ids, 6).unwrap().is_empty());
+    }
+}
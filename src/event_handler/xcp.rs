@@ -0,0 +1,921 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use rfd::FileDialog;
+use slint::{Model, SharedString, VecModel, Weak};
+
+use crate::slint_generatedAppWindow::{AppWindow, XcpVariable};
+
+use super::{EVEN_COLOR, ODD_COLOR};
+
+const XCP_TIMEOUT: Duration = Duration::from_millis(200);
+const XCP_PLOT_LEN: usize = 200;
+const XCP_UI_REFRESH: Duration = Duration::from_millis(100);
+pub const XCP_NO_ID: u32 = u32::MAX;
+
+// XCP command codes (ASAM MCD-1 XCP)
+const CONNECT: u8 = 0xFF;
+const DISCONNECT: u8 = 0xFE;
+const GET_STATUS: u8 = 0xFD;
+const SHORT_UPLOAD: u8 = 0xF4;
+const SET_DAQ_PTR: u8 = 0xE2;
+const WRITE_DAQ: u8 = 0xE1;
+const SET_DAQ_LIST_MODE: u8 = 0xE0;
+const START_STOP_DAQ_LIST: u8 = 0xDE;
+const START_STOP_SYNCH: u8 = 0xDD;
+const FREE_DAQ: u8 = 0xD6;
+const ALLOC_DAQ: u8 = 0xD5;
+const ALLOC_ODT: u8 = 0xD4;
+const ALLOC_ODT_ENTRY: u8 = 0xD3;
+
+const PID_RES: u8 = 0xFF;
+const PID_ERR: u8 = 0xFE;
+
+#[derive(Clone, Copy, PartialEq)]
+enum XcpDataType {
+    UByte,
+    SByte,
+    UWord,
+    SWord,
+    ULong,
+    SLong,
+    Float32,
+}
+
+impl XcpDataType {
+    fn from_a2l(name: &str) -> Option<Self> {
+        match name {
+            "UBYTE" => Some(Self::UByte),
+            "SBYTE" => Some(Self::SByte),
+            "UWORD" => Some(Self::UWord),
+            "SWORD" => Some(Self::SWord),
+            "ULONG" => Some(Self::ULong),
+            "SLONG" => Some(Self::SLong),
+            "FLOAT32_IEEE" => Some(Self::Float32),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::UByte | Self::SByte => 1,
+            Self::UWord | Self::SWord => 2,
+            Self::ULong | Self::SLong | Self::Float32 => 4,
+        }
+    }
+
+    fn decode(&self, data: &[u8], is_big_endian: bool) -> f64 {
+        let mut bytes = [0u8; 4];
+        bytes[..self.size()].copy_from_slice(&data[..self.size()]);
+        if is_big_endian {
+            bytes[..self.size()].reverse();
+        }
+        match self {
+            Self::UByte => bytes[0] as f64,
+            Self::SByte => bytes[0] as i8 as f64,
+            Self::UWord => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::SWord => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::ULong => u32::from_le_bytes(bytes) as f64,
+            Self::SLong => i32::from_le_bytes(bytes) as f64,
+            Self::Float32 => f32::from_le_bytes(bytes) as f64,
+        }
+    }
+}
+
+/// A2L conversion from ECU internal value to physical value.
+#[derive(Clone, Copy)]
+enum Conversion {
+    Identical,
+    /// phys = a * int + b
+    Linear(f64, f64),
+    /// int = (b * phys + c) / (e * phys + f), i.e. RAT_FUNC with a = d = 0
+    Rational(f64, f64, f64, f64),
+}
+
+impl Conversion {
+    fn apply(&self, value: f64) -> f64 {
+        match *self {
+            Self::Identical => value,
+            Self::Linear(a, b) => a * value + b,
+            Self::Rational(b, c, e, f) => (c - f * value) / (e * value - b),
+        }
+    }
+}
+
+struct Measurement {
+    name: String,
+    address: u32,
+    data_type: XcpDataType,
+    conversion: Conversion,
+}
+
+#[derive(Default)]
+struct A2lFile {
+    measurements: Vec<Measurement>,
+    can_id_master: Option<u32>,
+    can_id_slave: Option<u32>,
+}
+
+impl A2lFile {
+    fn parse(content: &str) -> Self {
+        let tokens = Self::tokenize(content);
+        let mut a2l = A2lFile::default();
+        let mut conversions: HashMap<String, Conversion> = HashMap::new();
+        // Measurements refer to conversions by name, resolve after parsing
+        let mut pending: Vec<(Measurement, String)> = Vec::new();
+        let mut index = 0;
+        while index < tokens.len() {
+            match tokens[index].as_str() {
+                "/begin" if tokens.get(index + 1).map(String::as_str) == Some("MEASUREMENT") => {
+                    // /begin MEASUREMENT <name> <description> <datatype> <conversion> ...
+                    let name = tokens.get(index + 2).cloned().unwrap_or_default();
+                    let data_type = tokens.get(index + 4).and_then(|t| XcpDataType::from_a2l(t));
+                    let conversion = tokens.get(index + 5).cloned().unwrap_or_default();
+                    let mut address = None;
+                    index += 6;
+                    while index < tokens.len() {
+                        if tokens[index] == "/end"
+                            && tokens.get(index + 1).map(String::as_str) == Some("MEASUREMENT")
+                        {
+                            break;
+                        }
+                        if tokens[index] == "ECU_ADDRESS" {
+                            address = tokens.get(index + 1).and_then(|t| parse_a2l_number(t));
+                        }
+                        index += 1;
+                    }
+                    if let (Some(data_type), Some(address)) = (data_type, address) {
+                        pending.push((
+                            Measurement {
+                                name,
+                                address,
+                                data_type,
+                                conversion: Conversion::Identical,
+                            },
+                            conversion,
+                        ));
+                    }
+                }
+                "/begin" if tokens.get(index + 1).map(String::as_str) == Some("COMPU_METHOD") => {
+                    let name = tokens.get(index + 2).cloned().unwrap_or_default();
+                    index += 3;
+                    while index < tokens.len() {
+                        if tokens[index] == "/end"
+                            && tokens.get(index + 1).map(String::as_str) == Some("COMPU_METHOD")
+                        {
+                            break;
+                        }
+                        let coeffs = |count: usize| -> Vec<f64> {
+                            tokens[index + 1..]
+                                .iter()
+                                .take(count)
+                                .filter_map(|t| t.parse::<f64>().ok())
+                                .collect()
+                        };
+                        match tokens[index].as_str() {
+                            "COEFFS_LINEAR" => {
+                                if let [a, b] = coeffs(2)[..] {
+                                    conversions.insert(name.clone(), Conversion::Linear(a, b));
+                                }
+                            }
+                            "COEFFS" => {
+                                if let [a, b, c, d, e, f] = coeffs(6)[..] {
+                                    if a.abs() < f64::EPSILON && d.abs() < f64::EPSILON {
+                                        conversions
+                                            .insert(name.clone(), Conversion::Rational(b, c, e, f));
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                        index += 1;
+                    }
+                }
+                "CAN_ID_MASTER" => {
+                    a2l.can_id_master = tokens.get(index + 1).and_then(|t| parse_a2l_number(t));
+                }
+                "CAN_ID_SLAVE" => {
+                    a2l.can_id_slave = tokens.get(index + 1).and_then(|t| parse_a2l_number(t));
+                }
+                _ => {}
+            }
+            index += 1;
+        }
+        a2l.measurements = pending
+            .into_iter()
+            .map(|(mut measurement, conversion)| {
+                if let Some(conversion) = conversions.get(&conversion) {
+                    measurement.conversion = *conversion;
+                }
+                measurement
+            })
+            .collect();
+        a2l
+    }
+
+    fn tokenize(content: &str) -> Vec<String> {
+        let mut tokens = Vec::new();
+        let mut chars = content.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    let mut token = String::new();
+                    for c in chars.by_ref() {
+                        if c == '"' {
+                            break;
+                        }
+                        token.push(c);
+                    }
+                    tokens.push(token);
+                }
+                '/' if chars.peek() == Some(&'/') => {
+                    // Skip line comment
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    // Skip block comment
+                    let mut previous = ' ';
+                    for c in chars.by_ref() {
+                        if previous == '*' && c == '/' {
+                            break;
+                        }
+                        previous = c;
+                    }
+                }
+                c if c.is_whitespace() => {}
+                c => {
+                    let mut token = String::from(c);
+                    while let Some(&c) = chars.peek() {
+                        if c.is_whitespace() || c == '"' {
+                            break;
+                        }
+                        token.push(c);
+                        chars.next();
+                    }
+                    tokens.push(token);
+                }
+            }
+        }
+        tokens
+    }
+}
+
+fn parse_a2l_number(token: &str) -> Option<u32> {
+    match token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => token.parse().ok(),
+    }
+}
+
+/// One ODT entry of the DAQ list: which measurement and where it is in the DTO.
+struct OdtEntry {
+    measurement: usize,
+    offset: usize,
+}
+
+enum XcpCommand {
+    LoadA2l(PathBuf),
+    Connect(String, String),
+    Disconnect,
+    GetStatus,
+    ShortUpload(String, i32),
+    SelectVariable(usize, bool),
+    Daq(bool),
+}
+
+pub struct XcpHandler<'a> {
+    pub ui_handle: &'a Weak<AppWindow>,
+    pub xcp_rx: Receiver<(u32, Vec<u8>)>,
+    pub xcp_dto_id: Arc<AtomicU32>,
+    pub transmit_tx: Sender<(bool, u32, Vec<u8>)>,
+}
+
+impl<'a> XcpHandler<'a> {
+    pub fn run(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let tx_clone = tx.clone();
+            ui.on_xcp_open_a2l(move || {
+                if let Some(path) = FileDialog::new()
+                    .add_filter("a2l", &["a2l", "A2L"])
+                    .set_directory("./")
+                    .pick_file()
+                {
+                    let _ = tx_clone.send(XcpCommand::LoadA2l(path));
+                }
+            });
+            let tx_clone = tx.clone();
+            ui.on_xcp_connect(move |cro, dto| {
+                let _ = tx_clone.send(XcpCommand::Connect(cro.into(), dto.into()));
+            });
+            let tx_clone = tx.clone();
+            ui.on_xcp_disconnect(move || {
+                let _ = tx_clone.send(XcpCommand::Disconnect);
+            });
+            let tx_clone = tx.clone();
+            ui.on_xcp_get_status(move || {
+                let _ = tx_clone.send(XcpCommand::GetStatus);
+            });
+            let tx_clone = tx.clone();
+            ui.on_xcp_short_upload(move |address, size| {
+                let _ = tx_clone.send(XcpCommand::ShortUpload(address.into(), size));
+            });
+            let tx_clone = tx.clone();
+            ui.on_xcp_select_variable(move |index, checked| {
+                let _ = tx_clone.send(XcpCommand::SelectVariable(index as usize, checked));
+            });
+            ui.on_xcp_daq(move |en| {
+                let _ = tx.send(XcpCommand::Daq(en));
+            });
+        });
+
+        let mut session = XcpSession::default();
+        loop {
+            while let Ok(command) = rx.try_recv() {
+                let status = match command {
+                    XcpCommand::LoadA2l(path) => self.load_a2l(&mut session, path),
+                    XcpCommand::Connect(cro, dto) => self.connect(&mut session, &cro, &dto),
+                    XcpCommand::Disconnect => {
+                        let _ = self.command(&session, &[DISCONNECT]);
+                        session.daq_running = false;
+                        self.xcp_dto_id.store(XCP_NO_ID, Ordering::Relaxed);
+                        "Disconnected".to_string()
+                    }
+                    XcpCommand::GetStatus => match self.command(&session, &[GET_STATUS]) {
+                        Ok(response) if response.len() >= 2 => format!(
+                            "Session status 0x{:02X}, resource protection 0x{:02X}",
+                            response[1],
+                            response.get(2).unwrap_or(&0)
+                        ),
+                        Ok(_) => "Invalid GET_STATUS response".to_string(),
+                        Err(e) => e,
+                    },
+                    XcpCommand::ShortUpload(address, size) => {
+                        self.short_upload(&session, &address, size)
+                    }
+                    XcpCommand::SelectVariable(index, checked) => {
+                        if let Some(selected) = session.selected.get_mut(index) {
+                            *selected = checked;
+                        }
+                        continue;
+                    }
+                    XcpCommand::Daq(en) => {
+                        if en {
+                            self.start_daq(&mut session)
+                        } else {
+                            session.daq_running = false;
+                            let _ = self.command(&session, &[START_STOP_SYNCH, 0x00]);
+                            "DAQ stopped".to_string()
+                        }
+                    }
+                };
+                let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+                    ui.set_xcp_status(status.into());
+                });
+            }
+            if session.daq_running {
+                self.process_daq(&mut session);
+            } else {
+                std::thread::sleep(Duration::from_millis(50));
+            }
+        }
+    }
+
+    fn load_a2l(&self, session: &mut XcpSession, path: PathBuf) -> String {
+        let content = match fs::read(&path) {
+            Ok(content) => String::from_utf8_lossy(&content).to_string(),
+            Err(e) => return format!("Failed to read {}: {}", path.display(), e),
+        };
+        let a2l = A2lFile::parse(&content);
+        let variables: Vec<XcpVariable> = a2l
+            .measurements
+            .iter()
+            .enumerate()
+            .map(|(index, measurement)| XcpVariable {
+                name: SharedString::from(measurement.name.as_str()),
+                address: SharedString::from(format!("0x{:08X}", measurement.address)),
+                value: SharedString::from("-"),
+                plot: SharedString::from("M 0 100"),
+                selected: false,
+                color: if index % 2 == 0 {
+                    EVEN_COLOR
+                } else {
+                    ODD_COLOR
+                },
+            })
+            .collect();
+        let status = format!(
+            "Loaded {} measurements from {}",
+            variables.len(),
+            path.display()
+        );
+        let cro = a2l.can_id_master.map(|id| format!("{:X}", id));
+        let dto = a2l.can_id_slave.map(|id| format!("{:X}", id));
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            ui.set_xcp_variables(Rc::new(VecModel::from(variables)).into());
+            if let Some(cro) = cro {
+                ui.set_xcp_cro_id(cro.into());
+            }
+            if let Some(dto) = dto {
+                ui.set_xcp_dto_id(dto.into());
+            }
+        });
+        session.selected = vec![false; a2l.measurements.len()];
+        session.history = vec![VecDeque::with_capacity(XCP_PLOT_LEN); a2l.measurements.len()];
+        session.measurements = a2l.measurements;
+        session.daq_running = false;
+        status
+    }
+
+    fn connect(&self, session: &mut XcpSession, cro: &str, dto: &str) -> String {
+        let (cro, dto) = match (
+            u32::from_str_radix(cro.trim(), 16),
+            u32::from_str_radix(dto.trim(), 16),
+        ) {
+            (Ok(cro), Ok(dto)) => (cro, dto),
+            _ => return "Invalid CRO/DTO CAN ID".to_string(),
+        };
+        session.cro_id = cro;
+        session.daq_running = false;
+        self.xcp_dto_id.store(dto, Ordering::Relaxed);
+        match self.command(session, &[CONNECT, 0x00]) {
+            Ok(response) if response.len() >= 8 => {
+                // COMM_MODE_BASIC bit 0: byte order (0 = Intel, 1 = Motorola)
+                session.is_big_endian = response[2] & 0x01 != 0;
+                session.max_cto = response[3] as usize;
+                session.max_dto = if session.is_big_endian {
+                    u16::from_be_bytes([response[4], response[5]])
+                } else {
+                    u16::from_le_bytes([response[4], response[5]])
+                } as usize;
+                format!(
+                    "Connected: resource 0x{:02X}, MAX_CTO {}, MAX_DTO {}, protocol v{}, transport v{}",
+                    response[1], session.max_cto, session.max_dto, response[6], response[7]
+                )
+            }
+            Ok(_) => "Invalid CONNECT response".to_string(),
+            Err(e) => e,
+        }
+    }
+
+    fn short_upload(&self, session: &XcpSession, address: &str, size: i32) -> String {
+        let Ok(address) = u32::from_str_radix(address.trim(), 16) else {
+            return "Invalid address".to_string();
+        };
+        // On CAN the whole response has to fit in one CTO
+        let size = (size.max(1) as usize).min(session.max_cto.saturating_sub(1).max(1));
+        let mut command = vec![SHORT_UPLOAD, size as u8, 0x00, 0x00];
+        command.extend(session.to_bytes(address));
+        match self.command(session, &command) {
+            Ok(response) => format!(
+                "0x{:08X}: {}",
+                address,
+                response
+                    .iter()
+                    .skip(1)
+                    .take(size)
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            ),
+            Err(e) => e,
+        }
+    }
+
+    fn start_daq(&self, session: &mut XcpSession) -> String {
+        let odts = session.pack_odts();
+        if odts.is_empty() {
+            return "No variables selected for DAQ".to_string();
+        }
+        if odts.len() > 0xFC {
+            return "Too many variables for one DAQ list".to_string();
+        }
+
+        let daq = session.to_bytes16(0);
+        let mut commands = vec![
+            vec![FREE_DAQ],
+            [vec![ALLOC_DAQ, 0x00], session.to_bytes16(1).to_vec()].concat(),
+            [vec![ALLOC_ODT, 0x00], daq.to_vec(), vec![odts.len() as u8]].concat(),
+        ];
+        for (odt_number, odt) in odts.iter().enumerate() {
+            commands.push(
+                [
+                    vec![ALLOC_ODT_ENTRY, 0x00],
+                    daq.to_vec(),
+                    vec![odt_number as u8, odt.len() as u8],
+                ]
+                .concat(),
+            );
+        }
+        for (odt_number, odt) in odts.iter().enumerate() {
+            commands.push(
+                [
+                    vec![SET_DAQ_PTR, 0x00],
+                    daq.to_vec(),
+                    vec![odt_number as u8, 0],
+                ]
+                .concat(),
+            );
+            for entry in odt {
+                let measurement = &session.measurements[entry.measurement];
+                // Bit offset 0xFF: whole element, address extension 0
+                commands.push(
+                    [
+                        vec![WRITE_DAQ, 0xFF, measurement.data_type.size() as u8, 0x00],
+                        session.to_bytes(measurement.address).to_vec(),
+                    ]
+                    .concat(),
+                );
+            }
+        }
+        // Mode 0x00, event channel 0, prescaler 1, priority 0
+        commands.push(
+            [
+                vec![SET_DAQ_LIST_MODE, 0x00],
+                daq.to_vec(),
+                session.to_bytes16(0).to_vec(),
+                vec![0x01, 0x00],
+            ]
+            .concat(),
+        );
+        // Select the DAQ list, then start all selected lists synchronously
+        commands.push([vec![START_STOP_DAQ_LIST, 0x02], daq.to_vec()].concat());
+        commands.push(vec![START_STOP_SYNCH, 0x01]);
+        let mut first_pid = 0;
+        for command in commands {
+            match self.command(session, &command) {
+                // Selecting the DAQ list returns the PID of its first ODT
+                Ok(response) if command[0] == START_STOP_DAQ_LIST => {
+                    first_pid = response.get(1).copied().unwrap_or_default();
+                }
+                Ok(_) => {}
+                Err(e) => return format!("DAQ setup failed at 0x{:02X}: {}", command[0], e),
+            }
+        }
+        session.odts = odts;
+        session.first_pid = first_pid;
+        session.daq_running = true;
+        session.last_refresh = Instant::now();
+        format!("DAQ started with {} ODTs", session.odts.len())
+    }
+
+    fn process_daq(&self, session: &mut XcpSession) {
+        while let Ok((_, data)) = self.xcp_rx.recv_timeout(Duration::from_millis(10)) {
+            session.decode_dto(&data);
+            if session.last_refresh.elapsed() >= XCP_UI_REFRESH {
+                break;
+            }
+        }
+        if session.last_refresh.elapsed() < XCP_UI_REFRESH {
+            return;
+        }
+        session.last_refresh = Instant::now();
+        let updates: Vec<(usize, String, String)> = session
+            .history
+            .iter()
+            .enumerate()
+            .filter_map(|(index, history)| {
+                history
+                    .back()
+                    .map(|value| (index, format!("{:.3}", value), Self::plot_path(history)))
+            })
+            .collect();
+        let _ = self.ui_handle.upgrade_in_event_loop(move |ui| {
+            let variables = ui.get_xcp_variables();
+            for (index, value, plot) in updates {
+                if let Some(mut variable) = variables.row_data(index) {
+                    variable.value = value.into();
+                    variable.plot = plot.into();
+                    variables.set_row_data(index, variable);
+                }
+            }
+        });
+    }
+
+    fn plot_path(history: &VecDeque<f64>) -> String {
+        let min = history.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = history.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let range = if max - min > f64::EPSILON {
+            max - min
+        } else {
+            1.0
+        };
+        let step = 200.0 / XCP_PLOT_LEN as f64;
+        history
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                format!(
+                    "{} {:.1} {:.1}",
+                    if index == 0 { "M" } else { "L" },
+                    index as f64 * step,
+                    100.0 - (value - min) / range * 100.0
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Send a CTO and wait for the positive response, DAQ packets in between are skipped.
+    fn command(&self, session: &XcpSession, command: &[u8]) -> Result<Vec<u8>, String> {
+        if self.xcp_dto_id.load(Ordering::Relaxed) == XCP_NO_ID {
+            return Err("Not connected".to_string());
+        }
+        // Drop stale responses of timed out commands
+        while self.xcp_rx.try_recv().is_ok() {}
+        let _ = self
+            .transmit_tx
+            .send((session.cro_id > 0x7FF, session.cro_id, command.to_vec()));
+        let start = Instant::now();
+        while start.elapsed() < XCP_TIMEOUT {
+            match self.xcp_rx.recv_timeout(XCP_TIMEOUT) {
+                Ok((_, data)) => match data.first() {
+                    Some(&PID_RES) => return Ok(data),
+                    Some(&PID_ERR) => {
+                        return Err(format!("ERR_CODE 0x{:02X}", data.get(1).unwrap_or(&0)))
+                    }
+                    _ => continue,
+                },
+                Err(_) => break,
+            }
+        }
+        Err("Timeout".to_string())
+    }
+}
+
+struct XcpSession {
+    cro_id: u32,
+    is_big_endian: bool,
+    max_cto: usize,
+    max_dto: usize,
+    measurements: Vec<Measurement>,
+    selected: Vec<bool>,
+    history: Vec<VecDeque<f64>>,
+    odts: Vec<Vec<OdtEntry>>,
+    first_pid: u8,
+    daq_running: bool,
+    last_refresh: Instant,
+}
+
+impl Default for XcpSession {
+    fn default() -> Self {
+        Self {
+            cro_id: 0,
+            is_big_endian: false,
+            max_cto: 8,
+            max_dto: 8,
+            measurements: Vec::default(),
+            selected: Vec::default(),
+            history: Vec::default(),
+            odts: Vec::default(),
+            first_pid: 0,
+            daq_running: false,
+            last_refresh: Instant::now(),
+        }
+    }
+}
+
+impl XcpSession {
+    fn pack_odts(&self) -> Vec<Vec<OdtEntry>> {
+        // Pack selected measurements into ODTs, first byte of each DTO is the PID
+        let odt_size = self.max_dto.saturating_sub(1);
+        let mut odts: Vec<Vec<OdtEntry>> = Vec::new();
+        let mut used = odt_size;
+        for (index, measurement) in self.measurements.iter().enumerate() {
+            if !self.selected[index] || measurement.data_type.size() > odt_size {
+                continue;
+            }
+            if used + measurement.data_type.size() > odt_size {
+                odts.push(Vec::new());
+                used = 0;
+            }
+            odts.last_mut().unwrap().push(OdtEntry {
+                measurement: index,
+                offset: used + 1,
+            });
+            used += measurement.data_type.size();
+        }
+        odts
+    }
+
+    fn decode_dto(&mut self, data: &[u8]) {
+        let Some(odt) = data
+            .first()
+            .and_then(|pid| pid.checked_sub(self.first_pid))
+            .and_then(|odt| self.odts.get(odt as usize))
+        else {
+            return;
+        };
+        for entry in odt {
+            let measurement = &self.measurements[entry.measurement];
+            let Some(raw) = data.get(entry.offset..entry.offset + measurement.data_type.size())
+            else {
+                continue;
+            };
+            let value = measurement
+                .conversion
+                .apply(measurement.data_type.decode(raw, self.is_big_endian));
+            let history = &mut self.history[entry.measurement];
+            if history.len() >= XCP_PLOT_LEN {
+                history.pop_front();
+            }
+            history.push_back(value);
+        }
+    }
+
+    fn to_bytes(&self, value: u32) -> [u8; 4] {
+        if self.is_big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+
+    fn to_bytes16(&self, value: u16) -> [u8; 2] {
+        if self.is_big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const A2L: &str = r#"
+        /* Test ECU */
+        // /begin MEASUREMENT Commented "" UBYTE NO_COMPU_METHOD 0 0 0 255 ECU_ADDRESS 0x1 /end MEASUREMENT
+        ASAP2_VERSION 1 71
+        /begin PROJECT Test ""
+          /begin MODULE Ecu ""
+            /begin IF_DATA XCP
+              /begin XCP_ON_CAN 0x0100
+                CAN_ID_MASTER 0x7E0 // CRO
+                CAN_ID_SLAVE 2024
+              /end XCP_ON_CAN
+            /end IF_DATA
+            /begin COMPU_METHOD CM_Speed "km/h" LINEAR "%6.2" "km/h"
+              COEFFS_LINEAR 0.5 -10
+            /end COMPU_METHOD
+            /begin COMPU_METHOD CM_Temp "" RAT_FUNC "%6.1" "degC"
+              COEFFS 0 2 80 0 0 1
+            /end COMPU_METHOD
+            /begin MEASUREMENT Speed "vehicle speed" UWORD CM_Speed 0 0 -10 300
+              ECU_ADDRESS 0x20001000
+            /end MEASUREMENT
+            /begin MEASUREMENT Temp "" SBYTE CM_Temp 0 0 -40 215
+              ECU_ADDRESS 0x20001004 // internal RAM
+            /end MEASUREMENT
+            /begin MEASUREMENT Counter "" ULONG NO_COMPU_METHOD 0 0 0 4294967295
+              ECU_ADDRESS 4096
+            /end MEASUREMENT
+          /end MODULE
+        /end PROJECT
+    "#;
+
+    fn measurement(data_type: XcpDataType, conversion: Conversion) -> Measurement {
+        Measurement {
+            name: String::new(),
+            address: 0,
+            data_type,
+            conversion,
+        }
+    }
+
+    fn session(measurements: Vec<Measurement>, selected: Vec<bool>) -> XcpSession {
+        XcpSession {
+            history: vec![VecDeque::new(); measurements.len()],
+            measurements,
+            selected,
+            ..XcpSession::default()
+        }
+    }
+
+    fn layout(odts: &[Vec<OdtEntry>]) -> Vec<Vec<(usize, usize)>> {
+        odts.iter()
+            .map(|odt| {
+                odt.iter()
+                    .map(|entry| (entry.measurement, entry.offset))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_a2l_file() {
+        let a2l = A2lFile::parse(A2L);
+        assert_eq!(a2l.can_id_master, Some(0x7E0));
+        assert_eq!(a2l.can_id_slave, Some(2024));
+        let names: Vec<&str> = a2l.measurements.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["Speed", "Temp", "Counter"]);
+        let addresses: Vec<u32> = a2l.measurements.iter().map(|m| m.address).collect();
+        assert_eq!(addresses, [0x20001000, 0x20001004, 4096]);
+        assert!(a2l.measurements[0].data_type == XcpDataType::UWord);
+        assert!(a2l.measurements[1].data_type == XcpDataType::SByte);
+        assert!(a2l.measurements[2].data_type == XcpDataType::ULong);
+        // COEFFS_LINEAR: phys = 0.5 * int - 10
+        assert_eq!(a2l.measurements[0].conversion.apply(200.0), 90.0);
+        // COEFFS 0 2 80 0 0 1: int = 2 * phys + 80
+        assert_eq!(a2l.measurements[1].conversion.apply(100.0), 10.0);
+        assert_eq!(a2l.measurements[2].conversion.apply(1234.0), 1234.0);
+    }
+
+    #[test]
+    fn skips_comments() {
+        let tokens = A2lFile::tokenize("A // B \"C\"\nD /* E\n F */ G \"H // I\"");
+        assert_eq!(tokens, ["A", "D", "G", "H // I"]);
+    }
+
+    #[test]
+    fn inverts_rational_function() {
+        // int = (2 * phys + 80) / (0.5 * phys + 1)
+        let conversion = Conversion::Rational(2.0, 80.0, 0.5, 1.0);
+        for phys in [-1.0, 0.0, 4.0, 25.0] {
+            let int = (2.0 * phys + 80.0) / (0.5 * phys + 1.0);
+            assert!((conversion.apply(int) - phys).abs() < 1e-9);
+        }
+        // COEFFS with a or d set are not rational functions of this form
+        let a2l = A2lFile::parse(
+            "/begin COMPU_METHOD CM \"\" RAT_FUNC \"%4.1\" \"\" COEFFS 1 2 80 0 0 1 /end COMPU_METHOD
+            /begin MEASUREMENT M \"\" UBYTE CM 0 0 0 255 ECU_ADDRESS 0x10 /end MEASUREMENT",
+        );
+        assert_eq!(a2l.measurements[0].conversion.apply(100.0), 100.0);
+    }
+
+    #[test]
+    fn packs_odts() {
+        let session = session(
+            vec![
+                measurement(XcpDataType::ULong, Conversion::Identical),
+                measurement(XcpDataType::UWord, Conversion::Identical),
+                measurement(XcpDataType::Float32, Conversion::Identical),
+                measurement(XcpDataType::UByte, Conversion::Identical),
+                measurement(XcpDataType::UByte, Conversion::Identical),
+                measurement(XcpDataType::SWord, Conversion::Identical),
+            ],
+            vec![true, true, true, true, false, true],
+        );
+        // 7 data bytes after the PID of an 8 byte DTO
+        assert_eq!(
+            layout(&session.pack_odts()),
+            [vec![(0, 1), (1, 5)], vec![(2, 1), (3, 5), (5, 6)]]
+        );
+        let session = XcpSession {
+            max_dto: 4,
+            ..session
+        };
+        // A ULONG doesn't fit into the 3 data bytes
+        assert_eq!(
+            layout(&session.pack_odts()),
+            [vec![(1, 1), (3, 3)], vec![(5, 1)]]
+        );
+    }
+
+    #[test]
+    fn decodes_dtos() {
+        let mut session = session(
+            vec![
+                measurement(XcpDataType::ULong, Conversion::Identical),
+                measurement(XcpDataType::SWord, Conversion::Linear(0.5, -10.0)),
+                measurement(XcpDataType::Float32, Conversion::Identical),
+            ],
+            vec![true; 3],
+        );
+        session.odts = session.pack_odts();
+        session.first_pid = 0x10;
+        session.decode_dto(&[0x10, 0x78, 0x56, 0x34, 0x12, 0x9C, 0xFF]);
+        session.decode_dto(&[0x11, 0x00, 0x00, 0xC0, 0x3F]);
+        assert_eq!(session.history[0], [f64::from(0x1234_5678u32)]);
+        assert_eq!(session.history[1], [-60.0]);
+        assert_eq!(session.history[2], [1.5]);
+        // PIDs of other DAQ lists and truncated entries are ignored
+        session.decode_dto(&[0x0F, 0x01, 0x00, 0x00, 0x00]);
+        session.decode_dto(&[0x12, 0x01, 0x00, 0x00, 0x00]);
+        session.decode_dto(&[0x10, 0x01, 0x00, 0x00, 0x00, 0x64]);
+        assert_eq!(session.history[0], [f64::from(0x1234_5678u32), 1.0]);
+        assert_eq!(session.history[1].len(), 1);
+        session.is_big_endian = true;
+        session.decode_dto(&[0x10, 0x12, 0x34, 0x56, 0x78, 0x00, 0x64]);
+        assert_eq!(session.history[0][2], f64::from(0x1234_5678u32));
+        assert_eq!(session.history[1][1], 40.0);
+    }
+}
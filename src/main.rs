@@ -1,17 +1,24 @@
 use std::io;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
 
 mod event_handler;
-use can_dbc::DBC;
-use event_handler::{CanHandler, DBCFile, DebugHandler, Init, PacketFilter};
+use event_handler::{
+    is_backend, AcceptanceFilter, AutoBaud, CanHandler, ChannelCommand, ChannelDbcs, CycleMonitor,
+    DBCFile, DbcTable, DebugHandler, FilterExpression, GatewayHandler, IdTraceModel, Init,
+    N2kDecoder, ObdHandler, PacketFilter, PgnDefinition, PgnFile, SignalUpdates, StatisticsHandler,
+    TimeFormat, TimeMode, TraceModel, TriggerHandler, UnknownIds, XcpHandler,
+    DEFAULT_TIMEOUT_FACTOR, XCP_NO_ID,
+};
 #[cfg(target_os = "windows")]
 use pcan_basic::bus::UsbBus;
 #[cfg(target_os = "linux")]
 use privilege_rs::privilege_request;
-#[cfg(target_os = "windows")]
-use slint::Model;
-use slint::SharedString;
+use slint::{Model, ModelRc, SharedString};
 #[cfg(target_os = "windows")]
 use winapi::um::wincon::FreeConsole;
 
@@ -26,9 +33,6 @@ async fn main() -> io::Result<()> {
     }
     let ui = AppWindow::new().unwrap();
 
-    let (tx, rx) = mpsc::channel::<DBC>();
-    // Wrap `rx` in an Arc<Mutex<>> so it can be shared safely across threads
-    let rx = Arc::new(Mutex::new(rx));
     #[cfg(target_os = "windows")]
     unsafe {
         FreeConsole(); // This detaches the console from the application
@@ -43,87 +47,423 @@ async fn main() -> io::Result<()> {
         init_event.run();
     });
 
-    let (start_tx_1, start_rx_1) = mpsc::channel();
-    let (start_tx_2, start_rx_2) = mpsc::channel();
+    let (start_tx, start_rx) = mpsc::channel();
 
-    // Handle start event
+    // Handle add channel event
     let ui_handle = ui.as_weak();
-    ui.on_start(move |_name, _index, bitrate| {
-        #[cfg(target_os = "linux")]
-        {
-            let ui = ui_handle.unwrap();
-            if _name.is_empty() {
-                ui.set_init_string(SharedString::from("No device found!!!"));
-            } else {
-                ui.set_is_init(true);
-                let _ = start_tx_1.send((_name.clone(), bitrate.clone()));
-                let _ = start_tx_2.send((_name, bitrate));
-            }
+    ui.on_add_channel(move |name, index, bitrate, data_bitrate, options| {
+        let init_event = Init {
+            ui_handle: &ui_handle,
+        };
+        if let Err(e) = init_event.add_channel(name, index, bitrate, data_bitrate, options) {
+            ui_handle.unwrap().set_init_string(SharedString::from(e));
         }
-        #[cfg(target_os = "windows")]
-        {
-            let ui = ui_handle.unwrap();
-            let get_device_handle = match ui.get_can_sockets().index.row_data(_index as usize) {
-                Some(device) => device,
-                None => {
-                    ui.set_init_string(SharedString::from("No device found!!!"));
-                    return;
-                }
+    });
+
+    let ui_handle = ui.as_weak();
+    ui.on_clear_channels(move || {
+        let init_event = Init {
+            ui_handle: &ui_handle,
+        };
+        init_event.clear_channels();
+    });
+
+    // Detect the bitrate of the selected interface
+    let ui_handle = ui.as_weak();
+    ui.on_auto_baud(move |name| {
+        let ui_handle = ui_handle.clone();
+        thread::spawn(move || {
+            let auto_baud = AutoBaud {
+                ui_handle: &ui_handle,
             };
-            let usb_can = UsbBus::try_from(get_device_handle as u16).unwrap();
-            let _ = start_tx_1.send((usb_can, bitrate.clone()));
-            let _ = start_tx_2.send((usb_can, bitrate));
-            ui.set_is_init(true);
-        }
+            auto_baud.run(&name);
+        });
     });
 
-    let (can_tx, can_rx) = mpsc::channel();
+    // Handle start event, opens all added channels or the selected interface if none was added
     let ui_handle = ui.as_weak();
-    tokio::spawn(async move {
-        if let Ok((can_if, bitrate)) = start_rx_1.recv() {
-            let mut can_handler = CanHandler {
-                #[cfg(target_os = "windows")]
-                iface: can_if,
-                #[cfg(target_os = "linux")]
-                iface: &can_if,
+    ui.on_start(move |name, index, bitrate, data_bitrate, options| {
+        let ui = ui_handle.unwrap();
+        if ui.get_channels().row_count() == 0 {
+            let init_event = Init {
                 ui_handle: &ui_handle,
-                mspc_rx: &rx,
-                bitrate: bitrate.to_string(),
-                dbc: None,
-                can_tx,
             };
-            loop {
-                can_handler.process_can_messages();
+            if let Err(e) = init_event.add_channel(name, index, bitrate, data_bitrate, options) {
+                ui.set_init_string(SharedString::from(e));
+                return;
             }
         }
+        let channels: Vec<CanChannel> = ui.get_channels().iter().collect();
+        if channels.is_empty() {
+            ui.set_init_string(SharedString::from("No device found!!!"));
+            return;
+        }
+        let mut start_channels = Vec::default();
+        // The channels send their frames to the debug trace while it is recording
+        let recording = ui.get_is_debug_en();
+        for channel in channels {
+            let backend = is_backend(&channel.name).then(|| channel.name.to_string());
+            // The channel still shows the messages of its DBC file
+            let keep_dbc = channel.messages.row_count() > 0;
+            #[cfg(target_os = "linux")]
+            start_channels.push((
+                channel.name.to_string(),
+                channel.bitrate,
+                channel.data_bitrate,
+                backend,
+                channel.options,
+                keep_dbc,
+                recording,
+            ));
+            #[cfg(target_os = "windows")]
+            {
+                let usb_can = if backend.is_some() {
+                    None
+                } else {
+                    let get_device_handle =
+                        match ui.get_can_sockets().index.row_data(channel.index as usize) {
+                            Some(device) => device,
+                            None => {
+                                ui.set_init_string(SharedString::from("No device found!!!"));
+                                return;
+                            }
+                        };
+                    Some(UsbBus::try_from(get_device_handle as u16).unwrap())
+                };
+                start_channels.push((
+                    usb_can,
+                    channel.bitrate,
+                    channel.data_bitrate,
+                    backend,
+                    channel.options,
+                    keep_dbc,
+                    recording,
+                ));
+            }
+        }
+        if let Some(channel) = ui.get_channels().row_data(0) {
+            ui.set_messages(channel.messages);
+        }
+        ui.set_is_init(true);
+        let _ = start_tx.send(start_channels);
+    });
+
+    // Show the DBC messages of the selected channel
+    let ui_handle = ui.as_weak();
+    ui.on_select_channel(move |index| {
+        let ui = ui_handle.unwrap();
+        if let Some(channel) = ui.get_channels().row_data(index as usize) {
+            ui.set_is_filter(false);
+            ui.set_filter_messages(ModelRc::default());
+            ui.set_messages(channel.messages);
+        }
     });
 
+    let (can_tx, can_rx) = mpsc::channel();
+    let (obd_tx, obd_rx) = mpsc::channel();
+    // OBD-II and XCP requests are sent on the first channel
+    let (transmit_tx, transmit_rx) = mpsc::channel();
+    let (xcp_tx, xcp_rx) = mpsc::channel();
+    let xcp_dto_id = Arc::new(AtomicU32::new(XCP_NO_ID));
+    let can_xcp_dto_id = xcp_dto_id.clone();
+    let xcp_transmit_tx = transmit_tx.clone();
+    let can_transmit_tx = transmit_tx.clone();
+    // PGN definitions are sent to the channel handlers once they are started, DBC files and the
+    // other runtime controls as channel commands
+    let trace_dbcs: ChannelDbcs = Arc::default();
+    let can_trace_dbcs = trace_dbcs.clone();
+    let n2k_tx: Arc<Mutex<Vec<mpsc::Sender<Vec<PgnDefinition>>>>> = Arc::default();
+    let can_n2k_tx = n2k_tx.clone();
+    let command_tx: Arc<Mutex<Vec<mpsc::Sender<ChannelCommand>>>> = Arc::default();
+    let can_command_tx = command_tx.clone();
+    let acceptance_filters: Arc<Mutex<Vec<AcceptanceFilter>>> = Arc::default();
+    let can_acceptance_filters = acceptance_filters.clone();
+    let (statistics_tx, statistics_rx) = mpsc::channel();
+    let cycle_timeout = Arc::new(AtomicU32::new(DEFAULT_TIMEOUT_FACTOR));
+    let can_cycle_timeout = cycle_timeout.clone();
+    let time_format: Arc<Mutex<TimeFormat>> = Arc::default();
+    let can_time_format = time_format.clone();
+    let trigger_time_format = time_format.clone();
+    let gateway_enabled = Arc::new(AtomicBool::new(false));
+    let (capture_tx, capture_rx) = mpsc::channel();
+    let capture_enabled = Arc::new(AtomicBool::new(false));
+    let can_capture_enabled = capture_enabled.clone();
     let ui_handle = ui.as_weak();
     tokio::spawn(async move {
-        if let Ok((_can_if, bitrate)) = start_rx_2.recv() {
-            let mut can_handler = DebugHandler {
+        // The first channel keeps its transmit queue and every channel its DBC file across
+        // disconnects, PGN definitions are applied to all channels
+        let mut transmit_rx = Some(transmit_rx);
+        let mut dbcs: Vec<Option<Arc<DbcTable>>> = Vec::default();
+        let mut pgn_definitions: Option<Vec<PgnDefinition>> = None;
+        while let Ok(start_channels) = start_rx.recv() {
+            let mut channel_transmit_tx = vec![can_transmit_tx.clone()];
+            let (gateway_tx, gateway_rx) = mpsc::channel();
+            can_n2k_tx.lock().unwrap().clear();
+            can_command_tx.lock().unwrap().clear();
+            can_trace_dbcs.lock().unwrap().clear();
+            let mut channel_threads = Vec::default();
+            for (channel, (can_if, bitrate, data_bitrate, backend, options, keep_dbc, recording)) in
+                start_channels.into_iter().enumerate()
+            {
+                let transmit_rx = transmit_rx.take().unwrap_or_else(|| {
+                    let (transmit_tx, transmit_rx) = mpsc::channel();
+                    channel_transmit_tx.push(transmit_tx);
+                    transmit_rx
+                });
+                let dbc = dbcs
+                    .get_mut(channel)
+                    .and_then(Option::take)
+                    .filter(|_| keep_dbc);
+                if let Some(dbc) = &dbc {
+                    can_trace_dbcs.lock().unwrap().insert(channel, dbc.clone());
+                }
+                let n2k = pgn_definitions.clone().map(N2kDecoder::new);
+                let (channel_n2k_tx, n2k_rx) = mpsc::channel();
+                can_n2k_tx.lock().unwrap().push(channel_n2k_tx);
+                let (channel_command_tx, command_rx) = mpsc::channel();
+                can_command_tx.lock().unwrap().push(channel_command_tx);
+                let ui_handle = ui_handle.clone();
+                let can_tx = can_tx.clone();
+                let obd_tx = obd_tx.clone();
+                let xcp_tx = xcp_tx.clone();
+                let xcp_dto_id = can_xcp_dto_id.clone();
+                let gateway_tx = gateway_tx.clone();
+                let statistics_tx = statistics_tx.clone();
+                let cycle_timeout = can_cycle_timeout.clone();
+                let time_format = can_time_format.clone();
+                let acceptance_filters = can_acceptance_filters.clone();
+                let capture_tx = capture_tx.clone();
+                let capture_enabled = can_capture_enabled.clone();
+                let can_gateway_enabled = gateway_enabled.clone();
+                // Each channel blocks on its own interface
+                channel_threads.push(thread::spawn(move || {
+                    let mut can_handler = CanHandler {
+                        channel,
+                        #[cfg(target_os = "windows")]
+                        iface: can_if,
+                        #[cfg(target_os = "linux")]
+                        iface: &can_if,
+                        backend,
+                        options,
+                        ui_handle: &ui_handle,
+                        bitrate: bitrate.to_string(),
+                        data_bitrate: data_bitrate.to_string(),
+                        cycle_monitor: dbc.as_ref().map(|dbc| CycleMonitor::new(dbc.dbc())),
+                        dbc,
+                        can_tx,
+                        recording,
+                        obd_tx,
+                        transmit_rx,
+                        xcp_tx,
+                        xcp_dto_id,
+                        gateway_tx,
+                        statistics_tx,
+                        gateway_enabled: can_gateway_enabled,
+                        n2k_rx,
+                        n2k,
+                        unknown_ids: UnknownIds::default(),
+                        cycle_timeout,
+                        time_format,
+                        command_rx,
+                        acceptance_filters,
+                        capture_tx,
+                        capture_enabled,
+                        signal_updates: SignalUpdates::default(),
+                        n2k_updates: SignalUpdates::default(),
+                        last_refresh: Instant::now(),
+                    };
+                    while can_handler.process_can_messages() {}
+                    (
+                        can_handler.transmit_rx,
+                        can_handler.dbc,
+                        can_handler.n2k.map(|n2k| n2k.definitions()),
+                    )
+                }));
+            }
+            // The gateway stops once all channels are disconnected
+            drop(gateway_tx);
+            CanHandler::register_transmit(&ui_handle, channel_transmit_tx.clone());
+            // Route frames between the channels
+            let mut gateway_handler = GatewayHandler {
                 ui_handle: &ui_handle,
-                bitrate: bitrate.to_string(),
-                filter: (0, 0xFFFFFFFF),
-                can_rx,
+                gateway_rx,
+                gateway_enabled: gateway_enabled.clone(),
+                transmit_tx: channel_transmit_tx,
             };
-            loop {
-                can_handler.run();
+            gateway_handler.run();
+            dbcs.clear();
+            for (channel, channel_thread) in channel_threads.into_iter().enumerate() {
+                let Ok((channel_transmit_rx, dbc, definitions)) = channel_thread.join() else {
+                    println!("ERR: Channel {} stopped unexpectedly", channel);
+                    dbcs.push(None);
+                    continue;
+                };
+                if channel == 0 {
+                    transmit_rx = Some(channel_transmit_rx);
+                }
+                dbcs.push(dbc);
+                if definitions.is_some() {
+                    pgn_definitions = definitions;
+                }
             }
         }
     });
 
+    let ui_handle = ui.as_weak();
+    let debug_command_tx = command_tx.clone();
+    tokio::spawn(async move {
+        let mut debug_handler = DebugHandler {
+            ui_handle: &ui_handle,
+            filter: Vec::default(),
+            expression: None,
+            dbcs: trace_dbcs.clone(),
+            can_rx,
+            command_tx: debug_command_tx,
+        };
+        loop {
+            debug_handler.run();
+        }
+    });
+
+    // Collect per-ID statistics of all channels
+    let ui_handle = ui.as_weak();
+    thread::spawn(move || {
+        let mut statistics_handler = StatisticsHandler {
+            ui_handle: &ui_handle,
+            statistics_rx,
+        };
+        statistics_handler.run();
+    });
+
+    // Save the events around a trigger condition
+    let ui_handle = ui.as_weak();
+    let trigger_dbcs = trace_dbcs.clone();
+    thread::spawn(move || {
+        let mut trigger_handler = TriggerHandler {
+            ui_handle: &ui_handle,
+            capture_rx,
+            capture_enabled,
+            dbcs: trigger_dbcs,
+            time_format: trigger_time_format,
+        };
+        trigger_handler.run();
+    });
+
+    // Handle OBD-II requests
+    let ui_handle = ui.as_weak();
+    thread::spawn(move || {
+        let mut obd_handler = ObdHandler {
+            ui_handle: &ui_handle,
+            obd_rx,
+            transmit_tx,
+        };
+        obd_handler.run();
+    });
+
+    // Handle XCP measurement
+    let ui_handle = ui.as_weak();
+    thread::spawn(move || {
+        let mut xcp_handler = XcpHandler {
+            ui_handle: &ui_handle,
+            xcp_rx,
+            xcp_dto_id,
+            transmit_tx: xcp_transmit_tx,
+        };
+        xcp_handler.run();
+    });
+
     // Handle open file event
     let ui_handle = ui.as_weak();
+    let dbc_command_tx = command_tx.clone();
+    let dbc_trace_dbcs = trace_dbcs.clone();
     ui.on_open_dbc_file(move || {
+        let ui = ui_handle.unwrap();
+        let command_tx = dbc_command_tx.lock().unwrap();
+        let Some(command_tx) = command_tx.get(ui.get_selected_channel() as usize) else {
+            return;
+        };
         let dbc_handle = DBCFile {
             ui_handle: &ui_handle,
-            mspc_tx: &tx.clone(),
+            command_tx,
+            trace_dbcs: &dbc_trace_dbcs,
         };
 
         dbc_handle.process_dbc_file();
     });
 
+    // Remove the DBC file of the selected channel
+    let ui_handle = ui.as_weak();
+    let unload_command_tx = command_tx.clone();
+    ui.on_unload_dbc(move || {
+        let ui = ui_handle.unwrap();
+        let channel = ui.get_selected_channel() as usize;
+        if let Some(command_tx) = unload_command_tx.lock().unwrap().get(channel) {
+            let _ = command_tx.send(ChannelCommand::UnloadDbc);
+        }
+        trace_dbcs.lock().unwrap().remove(&channel);
+        let channels = ui.get_channels();
+        if let Some(mut can_channel) = channels.row_data(channel) {
+            can_channel.messages = ModelRc::default();
+            channels.set_row_data(channel, can_channel);
+        }
+        ui.set_is_filter(false);
+        ui.set_filter_messages(ModelRc::default());
+        ui.set_messages(ModelRc::default());
+    });
+
+    // Handle open PGN definition file event
+    let ui_handle = ui.as_weak();
+    ui.on_open_pgn_file(move || {
+        let pgn_handle = PgnFile {
+            ui_handle: &ui_handle,
+            n2k_tx: &n2k_tx.lock().unwrap(),
+        };
+
+        pgn_handle.process_pgn_file();
+    });
+
+    // The debug trace is kept in a ring buffer model, frames are appended by the debug handler
+    let trace = Rc::new(TraceModel::default());
+    ui.set_raw_data(trace.clone().into());
+    let search_trace = trace.clone();
+    ui.on_trace_search(move |text, from, forward| {
+        search_trace
+            .search(&text, from, forward)
+            .map_or(-1, |row| row as i32)
+    });
+    let time_trace = trace.clone();
+    ui.on_trace_find_time(move |time| time_trace.find_time(&time).map_or(-1, |row| row as i32));
+    // The per-ID trace keeps one row per ID, updated together with the trace
+    let id_trace = Rc::new(IdTraceModel::default());
+    ui.set_id_trace(id_trace.clone().into());
+    let format_trace = trace.clone();
+    let format_id_trace = id_trace.clone();
+    // The export holds the frames shown in the trace, filters and expression already applied
+    let export_trace = trace.clone();
+    ui.on_trace_export(move || {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("log", &["log"])
+            .set_file_name("trace.log")
+            .save_file()
+        else {
+            return SharedString::default();
+        };
+        match export_trace.export(&path) {
+            Ok(count) => SharedString::from(format!("{} frames exported", count)),
+            Err(e) => SharedString::from(format!("Export failed: {}", e)),
+        }
+    });
+    ui.on_trace_clear(move || {
+        trace.clear();
+        id_trace.clear();
+    });
+    ui.on_set_time_format(move |mode, precision| {
+        let format = TimeFormat::new(TimeMode::from_index(mode), precision.max(0) as u32);
+        *time_format.lock().unwrap() = format;
+        format_trace.set_time_format(format);
+        format_id_trace.set_time_format(format);
+    });
+
     // Handle filter page
     let ui_handle = ui.as_weak();
     ui.on_filter_id(move |filter, is_check| {
@@ -135,6 +475,86 @@ async fn main() -> io::Result<()> {
         packet_filter.process_filter();
     });
 
+    // Acceptance filters are applied by the interfaces of all channels
+    let ui_handle = ui.as_weak();
+    let filter_command_tx = command_tx.clone();
+    ui.on_apply_acceptance_filter(move |filter| {
+        let ui = ui_handle.unwrap();
+        match AcceptanceFilter::parse(&filter) {
+            Ok(filter) => {
+                ui.set_acceptance_filter_status(SharedString::from(if filter.is_empty() {
+                    "All frames are accepted".to_string()
+                } else {
+                    format!("{} filter(s) applied", filter.len())
+                }));
+                *acceptance_filters.lock().unwrap() = filter;
+                for command_tx in filter_command_tx.lock().unwrap().iter() {
+                    let _ = command_tx.send(ChannelCommand::ApplyFilters);
+                }
+            }
+            Err(e) => ui.set_acceptance_filter_status(SharedString::from(e)),
+        }
+    });
+
+    // Syntax errors of the trace expression are shown while typing
+    ui.on_check_trace_expression(move |expression| {
+        if expression.trim().is_empty() {
+            return SharedString::default();
+        }
+        match FilterExpression::parse(&expression) {
+            Ok(_) => SharedString::default(),
+            Err(e) => SharedString::from(e),
+        }
+    });
+
+    // Re-run the interface setup of a channel, e.g. after bus-off
+    let reconnect_tx = command_tx.clone();
+    ui.on_reconnect(move |channel| {
+        if let Some(reconnect_tx) = reconnect_tx.lock().unwrap().get(channel as usize) {
+            let _ = reconnect_tx.send(ChannelCommand::Reconnect);
+        }
+    });
+
+    // Set up a channel again with another bitrate
+    let ui_handle = ui.as_weak();
+    let bitrate_command_tx = command_tx.clone();
+    ui.on_change_bitrate(move |channel, bitrate| {
+        let ui = ui_handle.unwrap();
+        let channels = ui.get_channels();
+        let Some(mut can_channel) = channels.row_data(channel as usize) else {
+            return;
+        };
+        if can_channel.bitrate == bitrate {
+            return;
+        }
+        if let Some(command_tx) = bitrate_command_tx.lock().unwrap().get(channel as usize) {
+            let _ = command_tx.send(ChannelCommand::SetBitrate(bitrate.to_string()));
+        }
+        can_channel.bitrate = bitrate;
+        channels.set_row_data(channel as usize, can_channel);
+    });
+
+    // Stop all channels and return to the device selection, DBC files and filters are kept
+    let ui_handle = ui.as_weak();
+    ui.on_disconnect(move || {
+        for command_tx in command_tx.lock().unwrap().iter() {
+            let _ = command_tx.send(ChannelCommand::Disconnect);
+        }
+        let ui = ui_handle.unwrap();
+        ui.set_init_string(SharedString::from("Select the CAN devices to connect"));
+        ui.set_is_init(false);
+    });
+
+    // Handle cycle time monitoring settings
+    ui.on_set_cycle_timeout(move |factor| {
+        cycle_timeout.store(factor.max(1) as u32, Ordering::Relaxed);
+    });
+
+    let ui_handle = ui.as_weak();
+    ui.on_clear_cycle_alarms(move || {
+        ui_handle.unwrap().set_cycle_alarms(ModelRc::default());
+    });
+
     ui.window().on_close_requested(|| {
         println!("Closing the application...");
         std::process::exit(0);